@@ -0,0 +1,296 @@
+//! Derive macro for the Serde trait of mangle_serde.
+//!
+//! Generates one `impl Serde<Profile>` and one `impl SerializeRef<Profile>` for every profile given
+//! with `#[mangle(profile = ...)]` on the struct, or for both `ReadableProfile` and `EfficientProfile` if none are given.
+//! SerializeRef converts each field by reference, so `&T` must convert into a Datum for the type of every field that is not nested.
+//! Fields are serialized as named entries in profiles based on MappedData,
+//! and as items, in declaration order, in profiles based on ArrayData.
+//!
+//! Fields can be of any type that converts to and from a Datum, or an `Option`, `Vec`, `HashMap` or `BTreeMap` of such types.
+//! Fields of other types are rejected, unless they are marked as nested or as an enum.
+//!
+//! The following attributes are supported on fields:
+//! * `rename = "name"`: Uses the given name for the entry instead of the name of the field
+//! * `skip`: Leaves the field out. It is left as its default value when deserializing
+//! * `default`: Uses the default value of the field if its entry is missing
+//! * `nested`: The field implements Serde with the same profile, or is a `Vec` or map of such values
+//! * `enum`: The field is an enum whose variants carry data, which implements Serde with the same profile and EnumVariants.
+//!   Profiles based on MappedData use external tagging
+//! * `profile = Profile`: Only applies the other attributes in the same `#[mangle(...)]` to the given profile
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{quote, quote_spanned};
+use syn::{Data, DeriveInput, Fields, GenericArgument, LitStr, Path, PathArguments, Type, parse_macro_input};
+use syn::spanned::Spanned;
+
+
+/// The attributes given in a single `#[mangle(...)]` on a field
+#[derive(Default)]
+struct FieldAttributes {
+	profile: Option<Path>,
+	rename: Option<String>,
+	skip: bool,
+	default: bool,
+	nested: bool,
+	is_enum: bool,
+}
+
+
+impl FieldAttributes {
+	/// Returns true if these attributes apply to the given profile.
+	/// Profiles are compared by name, so that paths to the same profile match
+	fn applies_to(&self, profile: &Path) -> bool {
+		match &self.profile {
+			Some(x) => x.segments.last().map(|x| &x.ident) == profile.segments.last().map(|x| &x.ident),
+			None => true
+		}
+	}
+}
+
+
+/// Parses every `#[mangle(...)]` on a field
+fn parse_field_attributes(attrs: &[syn::Attribute]) -> syn::Result<Vec<FieldAttributes>> {
+	let mut out = Vec::new();
+	for attr in attrs.iter().filter(|attr| attr.path().is_ident("mangle")) {
+		let mut parsed = FieldAttributes::default();
+		attr.parse_nested_meta(|meta| {
+			if meta.path.is_ident("profile") {
+				parsed.profile = Some(meta.value()?.parse()?);
+			} else if meta.path.is_ident("rename") {
+				parsed.rename = Some(meta.value()?.parse::<LitStr>()?.value());
+			} else if meta.path.is_ident("skip") {
+				parsed.skip = true;
+			} else if meta.path.is_ident("default") {
+				parsed.default = true;
+			} else if meta.path.is_ident("nested") {
+				parsed.nested = true;
+			} else if meta.path.is_ident("enum") {
+				parsed.is_enum = true;
+			} else {
+				return Err(meta.error("expected one of `profile`, `rename`, `skip`, `default`, `nested` or `enum`"));
+			}
+			Ok(())
+		})?;
+		out.push(parsed);
+	}
+	Ok(out)
+}
+
+
+/// Parses the profiles given with `#[mangle(profile = ...)]` on the struct
+fn parse_profiles(attrs: &[syn::Attribute]) -> syn::Result<Vec<Path>> {
+	let mut out = Vec::new();
+	for attr in attrs.iter().filter(|attr| attr.path().is_ident("mangle")) {
+		attr.parse_nested_meta(|meta| {
+			if meta.path.is_ident("profile") {
+				out.push(meta.value()?.parse()?);
+				Ok(())
+			} else {
+				Err(meta.error("expected `profile`"))
+			}
+		})?;
+	}
+	if out.is_empty() {
+		out.push(syn::parse_quote!(::mangle_serde::ReadableProfile));
+		out.push(syn::parse_quote!(::mangle_serde::EfficientProfile));
+	}
+	Ok(out)
+}
+
+
+/// The shape of the type of a field, which decides the methods used to serialize it
+#[derive(Copy, Clone, PartialEq)]
+enum FieldKind {
+	Single,
+	Optional,
+	Seq,
+	Map,
+}
+
+
+/// Returns the generic type arguments of the last segment of a path type, if it is named one of names
+fn container_args<'a>(ty: &'a Type, names: &[&str]) -> Option<Vec<&'a Type>> {
+	let segment = match ty {
+		Type::Path(x) if x.qself.is_none() => x.path.segments.last()?,
+		_ => return None
+	};
+	if !names.iter().any(|name| segment.ident == name) {
+		return None
+	}
+	match &segment.arguments {
+		PathArguments::AngleBracketed(args) => Some(args.args.iter()
+			.filter_map(|arg| match arg {
+				GenericArgument::Type(x) => Some(x),
+				_ => None
+			})
+			.collect()),
+		_ => None
+	}
+}
+
+
+/// Works out the kind of a field from its type.
+/// Returns an error naming the field if the type cannot be derived
+fn field_kind(name: &str, ty: &Type) -> syn::Result<FieldKind> {
+	let unsupported = |message: &str| Err(syn::Error::new_spanned(ty, format!("field `{}`: {}", name, message)));
+	let (kind, inner) = if let Some(args) = container_args(ty, &["Option"]) {
+		(FieldKind::Optional, args)
+	} else if let Some(args) = container_args(ty, &["Vec"]) {
+		(FieldKind::Seq, args)
+	} else if let Some(args) = container_args(ty, &["HashMap", "BTreeMap"]) {
+		(FieldKind::Map, args.into_iter().skip(1).collect())
+	} else {
+		match ty {
+			Type::Path(_) | Type::Paren(_) | Type::Group(_) | Type::Macro(_) => return Ok(FieldKind::Single),
+			_ => return unsupported("Serde cannot be derived for this type. Skip the field, or implement Serde by hand")
+		}
+	};
+	if inner.iter().any(|x| container_args(x, &["Option", "Vec", "HashMap", "BTreeMap"]).is_some()) {
+		return unsupported("containers inside of containers are not supported. Skip the field, or implement Serde by hand")
+	}
+	Ok(kind)
+}
+
+
+#[proc_macro_derive(Serde, attributes(mangle))]
+pub fn derive_serde(input: TokenStream) -> TokenStream {
+	let input = parse_macro_input!(input as DeriveInput);
+	match expand(input) {
+		Ok(x) => x.into(),
+		Err(e) => e.to_compile_error().into()
+	}
+}
+
+
+fn expand(input: DeriveInput) -> syn::Result<TokenStream2> {
+	let fields = match &input.data {
+		Data::Struct(x) => &x.fields,
+		_ => return Err(syn::Error::new_spanned(&input.ident, "Serde can only be derived for structs"))
+	};
+
+	// Each field along with its name, member expression and attributes
+	let mut parsed_fields = Vec::new();
+	for (index, field) in fields.iter().enumerate() {
+		let (name, member) = match (&field.ident, fields) {
+			(Some(ident), Fields::Named(_)) => (ident.to_string(), quote!(#ident)),
+			_ => {
+				let index = syn::Index::from(index);
+				(index.index.to_string(), quote!(#index))
+			}
+		};
+		let kind = field_kind(&name, &field.ty)?;
+		parsed_fields.push((name, member, field, kind, parse_field_attributes(&field.attrs)?));
+	}
+
+	let ident = &input.ident;
+	let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+	let mut impls = Vec::new();
+
+	for profile in parse_profiles(&input.attrs)? {
+		let mut calls = Vec::new();
+		let mut ref_calls = Vec::new();
+
+		for (field_name, member, field, kind, attributes) in &parsed_fields {
+			let mut name = field_name.clone();
+			let mut skip = false;
+			let mut default = false;
+			let mut nested = false;
+			let mut is_enum = false;
+			for attribute in attributes.iter().filter(|x| x.applies_to(&profile)) {
+				if let Some(rename) = &attribute.rename {
+					name = rename.clone();
+				}
+				skip |= attribute.skip;
+				default |= attribute.default;
+				nested |= attribute.nested;
+				is_enum |= attribute.is_enum;
+			}
+			if skip {
+				continue
+			}
+			let invalid = |message: &str| syn::Error::new_spanned(field, format!("field `{}`: {}", field_name, message));
+			if nested && is_enum {
+				return Err(invalid("a field cannot be both `nested` and `enum`"))
+			}
+			if nested && *kind == FieldKind::Optional {
+				return Err(invalid("optional nested fields are not supported. Skip the field, or implement Serde by hand"))
+			}
+			if is_enum && *kind != FieldKind::Single {
+				return Err(invalid("`enum` fields cannot be optional, sequences or maps"))
+			}
+
+			// Errors about missing trait implementations are reported at the type of the field
+			let span = field.ty.span();
+			let base = quote_spanned!(span=> <<#profile as ::std::ops::Deref>::Target as ::mangle_serde::DeriveProfile>);
+			let (call, ref_call) = match (kind, nested, is_enum) {
+				(FieldKind::Single, false, false) if default => (
+					quote_spanned!(span=> ::mangle_serde::DeriveProfile::derive_field_or_default(&mut **data, #name, &mut self.#member)),
+					quote_spanned!(span=> ::mangle_serde::DeriveProfile::derive_field_ref(&mut **data, #name, &self.#member))
+				),
+				(FieldKind::Single, false, false) => (
+					quote_spanned!(span=> ::mangle_serde::DeriveProfile::derive_field(&mut **data, #name, &mut self.#member)),
+					quote_spanned!(span=> ::mangle_serde::DeriveProfile::derive_field_ref(&mut **data, #name, &self.#member))
+				),
+				(FieldKind::Optional, _, _) => (
+					quote_spanned!(span=> ::mangle_serde::DeriveProfile::derive_optional_field(&mut **data, #name, &mut self.#member)),
+					quote_spanned!(span=> ::mangle_serde::DeriveProfile::derive_optional_field_ref(&mut **data, #name, &self.#member))
+				),
+				(FieldKind::Seq, false, _) => (
+					quote_spanned!(span=> ::mangle_serde::DeriveProfile::derive_seq_field(&mut **data, #name, &mut self.#member)),
+					quote_spanned!(span=> ::mangle_serde::DeriveProfile::derive_seq_field_ref(&mut **data, #name, &self.#member))
+				),
+				(FieldKind::Map, false, _) => (
+					quote_spanned!(span=> ::mangle_serde::DeriveProfile::derive_map_field(&mut **data, #name, &mut self.#member)),
+					quote_spanned!(span=> ::mangle_serde::DeriveProfile::derive_map_field_ref(&mut **data, #name, &self.#member))
+				),
+				(FieldKind::Single, true, _) => (
+					quote_spanned!(span=> #base::derive_nested_field(data, #name, &mut self.#member)),
+					quote_spanned!(span=> #base::derive_nested_field_ref(data, #name, &self.#member))
+				),
+				(FieldKind::Seq, true, _) => (
+					quote_spanned!(span=> #base::derive_nested_seq_field(data, #name, &mut self.#member)),
+					quote_spanned!(span=> #base::derive_nested_seq_field_ref(data, #name, &self.#member))
+				),
+				(FieldKind::Map, true, _) => (
+					quote_spanned!(span=> #base::derive_nested_map_field(data, #name, &mut self.#member)),
+					quote_spanned!(span=> #base::derive_nested_map_field_ref(data, #name, &self.#member))
+				),
+				(FieldKind::Single, false, true) => (
+					quote_spanned!(span=> #base::derive_enum_field(data, #name, &mut self.#member)),
+					quote_spanned!(span=> #base::derive_enum_field_ref(data, #name, &self.#member))
+				),
+			};
+			// Single values that are not nested handle defaults themselves, so that skip_defaults applies to them
+			if default && !(*kind == FieldKind::Single && !nested && !is_enum) {
+				calls.push(quote! {
+					match #call {
+						::std::result::Result::Err(::mangle_serde::DeserializationError::MissingField(field)) if field == #name => {
+							self.#member = ::std::default::Default::default();
+						}
+						x => x?
+					}
+				});
+			} else {
+				calls.push(quote!(#call?;));
+			}
+			ref_calls.push(quote!(#ref_call;));
+		}
+
+		impls.push(quote! {
+			impl #impl_generics ::mangle_serde::Serde<#profile> for #ident #ty_generics #where_clause {
+				fn serde(&mut self, data: &mut #profile) -> ::std::result::Result<(), ::mangle_serde::DeserializationError> {
+					#(#calls)*
+					::std::result::Result::Ok(())
+				}
+			}
+
+			impl #impl_generics ::mangle_serde::SerializeRef<#profile> for #ident #ty_generics #where_clause {
+				fn serialize_fields(&self, data: &mut #profile) {
+					#(#ref_calls)*
+				}
+			}
+		});
+	}
+
+	Ok(quote!(#(#impls)*))
+}
@@ -1,99 +1,695 @@
-use crate::{ArrayData, DataProfile, DeserializationError, ProfileFromData, ProfileToData, Serde};
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+use std::fmt::{Debug, Formatter};
+use std::io::{Read, Write};
+use std::ops::DerefMut;
+use std::rc::Rc;
+
+use crate::{ArrayData, DataProfile, MappedData, DeserializationError, DeserializeNew, ProfileFromData, ProfileToData, Serde, SerializationError, SerializeRef, TransformResult, serde_value};
 use crate::datum::{Datum, DatumSize, DatumType};
-use crate::profiles::{DatumArray};
+use crate::profiles::{ArrayOptions, ByteOrder, DatumArray, ItemSink, SerialItem, count_datum};
 
 
-fn split(arr: &mut Vec<u8>, size: usize) -> Option<Vec<u8>> {
-	if arr.len() < size {
-		return None
+/// Writes an unsigned integer as a LEB128 varint.
+/// Each byte holds 7 bits, starting from the least significant bits, and all bytes but the last have their high bit set
+fn write_varint(out: &mut Vec<u8>, mut n: u64) {
+	loop {
+		let byte = (n & 0x7f) as u8;
+		n >>= 7;
+		if n == 0 {
+			out.push(byte);
+			return
+		}
+		out.push(byte | 0x80);
 	}
-	let (first, last) = arr.split_at(size);
-	let first = first.to_vec();
-	*arr = last.to_vec();
-	Some(first)
 }
 
 
-fn split_arr<const SIZE: usize>(arr: &mut Vec<u8>) -> Option<[u8; SIZE]> {
-	if arr.len() < SIZE {
-		return None
-	}
-	let (first, last) = arr.split_at(SIZE);
-	let first = first.try_into().unwrap();
-	*arr = last.to_vec();
-	Some(first)
+/// Maps signed integers onto unsigned integers, so that numbers close to zero stay small as varints
+fn zigzag(n: i64) -> u64 {
+	((n << 1) ^ (n >> 63)) as u64
 }
 
 
-fn get_size(arr: &mut Vec<u8>, datum_size: DatumSize) -> Option<usize> {
-	if arr.is_empty() { return None }
-	Some(match datum_size {
-		DatumSize::U8 => arr.remove(0) as usize,
-		DatumSize::U16 => u16::from_be_bytes(split_arr::<2>(arr)?) as usize,
-		DatumSize::U32 => u32::from_be_bytes(split_arr::<4>(arr)?) as usize,
-		DatumSize::U64 => u64::from_be_bytes(split_arr::<8>(arr)?) as usize,
-	})
+/// Reverses zigzag
+fn unzigzag(n: u64) -> i64 {
+	((n >> 1) as i64) ^ -((n & 1) as i64)
 }
 
 
-impl DatumArray for Vec<u8> {
-	fn get_datum(&mut self, datum_type: DatumType, datum_size: DatumSize) -> Result<Datum, DeserializationError> {
+/// Converts a decoded varint into a narrower integer, failing if it does not fit
+fn narrow<N: TryFrom<T>, T: Copy + ToString>(n: T) -> Result<N, DeserializationError> {
+	N::try_from(n).map_err(|_| DeserializationError::NoMatch { field: "".into(), actual: n.to_string() })
+}
+
+
+/// How deeply maps and arrays can be nested in binary data.
+/// Deeper data is rejected, so that malformed data cannot overflow the stack
+const MAX_DEPTH: usize = 128;
+
+
+/// A source of binary data that is read from front to back
+trait ByteSource {
+	/// Returns the options of the data profile that is reading from this source
+	fn options(&self) -> ArrayOptions;
+	/// Reads the given number of bytes
+	fn take(&mut self, size: usize) -> Result<Vec<u8>, DeserializationError>;
+	/// Reads a fixed number of bytes
+	fn take_arr<const SIZE: usize>(&mut self) -> Result<[u8; SIZE], DeserializationError>;
+	
+	/// Reads a number of a fixed size, returning its bytes in big-endian order
+	fn take_number<const SIZE: usize>(&mut self) -> Result<[u8; SIZE], DeserializationError> {
+		let mut bytes = self.take_arr()?;
+		if self.options().byte_order == ByteOrder::LittleEndian {
+			bytes.reverse();
+		}
+		Ok(bytes)
+	}
+	/// Reads a LEB128 varint
+	fn take_varint(&mut self) -> Result<u64, DeserializationError> {
+		let mut n = 0;
+		for shift in (0..64).step_by(7) {
+			let [byte] = self.take_arr()?;
+			let bits = (byte & 0x7f) as u64;
+			// The tenth byte can only hold the highest bit
+			if shift == 63 && bits > 1 {
+				break
+			}
+			n |= bits << shift;
+			if byte & 0x80 == 0 {
+				return Ok(n)
+			}
+		}
+		Err(DeserializationError::NoMatch { field: "".into(), actual: "varint longer than 64 bits".into() })
+	}
+	/// Reads a length prefix of the given size
+	fn take_size(&mut self, datum_size: DatumSize) -> Result<usize, DeserializationError> {
+		if self.options().varint {
+			return narrow(self.take_varint()?)
+		}
+		Ok(match datum_size {
+			DatumSize::U8 => u8::from_be_bytes(self.take_number()?) as usize,
+			DatumSize::U16 => u16::from_be_bytes(self.take_number()?) as usize,
+			DatumSize::U32 => u32::from_be_bytes(self.take_number()?) as usize,
+			DatumSize::U64 => u64::from_be_bytes(self.take_number()?) as usize,
+		})
+	}
+	/// Reads a type tag
+	fn take_tag(&mut self) -> Result<DatumType, DeserializationError> {
+		let [tag] = self.take_arr()?;
+		DatumType::from_tag(tag).ok_or_else(|| DeserializationError::NoMatch { field: "".into(), actual: tag.to_string() })
+	}
+	/// Reads a datum that was written along with its type tag, at the given depth of nesting
+	fn take_tagged(&mut self, depth: usize) -> Result<Datum, DeserializationError> where Self: Sized {
+		let datum_type = self.take_tag()?;
+		self.take_datum(datum_type, DatumSize::U32, depth)
+	}
+	/// Reads an item of the given type.
+	/// If items are tagged, the tag must name the same type, so integers are not widened or narrowed from another width
+	fn take_item(&mut self, datum_type: DatumType, datum_size: DatumSize) -> Result<Datum, DeserializationError> where Self: Sized {
+		if self.options().tagged {
+			let tag = self.take_tag()?;
+			if tag != datum_type {
+				return Err(DeserializationError::InvalidType { field: "".into(), expected: datum_type.name(), actual: tag.name() })
+			}
+		}
+		self.take_datum(datum_type, datum_size, 0)
+	}
+	/// Reads a datum of the given type, at the given depth of nesting.
+	/// Fails if maps or arrays are nested deeper than MAX_DEPTH
+	fn take_datum(&mut self, datum_type: DatumType, datum_size: DatumSize, depth: usize) -> Result<Datum, DeserializationError> where Self: Sized {
+		let varint = self.options().varint;
 		Ok(match datum_type {
 			DatumType::String => {
-				let size = get_size(self, datum_size).ok_or(DeserializationError::UnexpectedEOF)?;
-				Datum::from(String::from_utf8(
-					split(self, size).ok_or(DeserializationError::UnexpectedEOF)?
-				)?)
+				let size = self.take_size(datum_size)?;
+				Datum::from(String::from_utf8(self.take(size)?)?)
+			},
+			DatumType::Bool => match self.take_arr()? {
+				[0] => Datum::Bool(false),
+				[1] => Datum::Bool(true),
+				[x] => return Err(DeserializationError::NoMatch { field: "".into(), actual: x.to_string() })
 			},
-			DatumType::U64 => Datum::from(u64::from_be_bytes(split_arr::<8>(self).ok_or(DeserializationError::UnexpectedEOF)?)),
-			_ => todo!()
+			DatumType::U8 => Datum::from(u8::from_be_bytes(self.take_arr()?)),
+			DatumType::U16 if varint => Datum::from(narrow::<u16, _>(self.take_varint()?)?),
+			DatumType::U32 if varint => Datum::from(narrow::<u32, _>(self.take_varint()?)?),
+			DatumType::U64 if varint => Datum::from(self.take_varint()?),
+			DatumType::I16 if varint => Datum::from(narrow::<i16, _>(unzigzag(self.take_varint()?))?),
+			DatumType::I32 if varint => Datum::from(narrow::<i32, _>(unzigzag(self.take_varint()?))?),
+			DatumType::I64 if varint => Datum::from(unzigzag(self.take_varint()?)),
+			DatumType::U16 => Datum::from(u16::from_be_bytes(self.take_number()?)),
+			DatumType::U32 => Datum::from(u32::from_be_bytes(self.take_number()?)),
+			DatumType::U64 => Datum::from(u64::from_be_bytes(self.take_number()?)),
+			DatumType::I8 => Datum::from(i8::from_be_bytes(self.take_arr()?)),
+			DatumType::I16 => Datum::from(i16::from_be_bytes(self.take_number()?)),
+			DatumType::I32 => Datum::from(i32::from_be_bytes(self.take_number()?)),
+			DatumType::I64 => Datum::from(i64::from_be_bytes(self.take_number()?)),
+			DatumType::F32 => Datum::from(f32::from_be_bytes(self.take_number()?)),
+			DatumType::F64 => Datum::from(f64::from_be_bytes(self.take_number()?)),
+			DatumType::Map | DatumType::Array if depth >= MAX_DEPTH => {
+				return Err(DeserializationError::NoMatch { field: "".into(), actual: format!("data nested deeper than {} levels", MAX_DEPTH) })
+			}
+			DatumType::Map => {
+				let len = self.take_size(datum_size)?;
+				let mut entries = HashMap::new();
+				for index in 0..len {
+					let key = self.take_tagged(depth + 1).transform(format!("[{}]", index))?;
+					let value = self.take_tagged(depth + 1).transform(key.to_key_string())?;
+					entries.insert(key, value);
+				}
+				Datum::Map(ProfileFromData::try_from(entries)?)
+			}
+			DatumType::Array => {
+				let len = self.take_size(datum_size)?;
+				let mut items = VecDeque::new();
+				for index in 0..len {
+					items.push_back(self.take_tagged(depth + 1).transform(format!("[{}]", index))?);
+				}
+				Datum::Array(ProfileFromData::try_from(items)?)
+			}
 		})
 	}
 }
 
 
-impl From<Datum> for Vec<u8> {
-	fn from(data: Datum) -> Self {
-		match data {
+/// A cursor over binary data, which reads items in place by advancing an offset.
+/// Reading never copies or moves the bytes that have not been read yet, so deserialization takes linear time.
+///
+/// Any buffer that can be viewed as a slice of bytes can be read, such as a Vec<u8>, a &'static [u8],
+/// or a reference counted buffer. Buffers that are cheap to clone make profiles that are based on the cursor cheap to clone too.
+/// A Vec<u8> is read through a cursor automatically, and other buffers can be read through `BinSerde::deserialize_bin_buffer`
+///
+/// Datums always own their strings, so fields that are read through Serde allocate every String they hold.
+/// Profiles cannot lend their data to the values they deserialize, so a field of type &'a str cannot be deserialized through Serde.
+/// To read strings without allocating, read them from a cursor over a &[u8] with next_str, which borrows them from the buffer
+#[derive(Debug, Clone)]
+pub struct BinCursor<B> {
+	bytes: B,
+	offset: usize,
+	options: ArrayOptions,
+}
+
+
+impl<B: AsRef<[u8]>> BinCursor<B> {
+	/// Creates a cursor at the start of the given buffer
+	pub fn new(bytes: B) -> Self {
+		Self { bytes, offset: 0, options: ArrayOptions::default() }
+	}
+	/// Returns the number of bytes that have been read
+	pub fn offset(&self) -> usize {
+		self.offset
+	}
+	/// Returns the bytes that have not been read yet
+	pub fn remaining_bytes(&self) -> &[u8] {
+		&self.bytes.as_ref()[self.offset..]
+	}
+	/// Reads the next datum of data that describes its own types, such as items of a profile with tagging enabled,
+	/// or data serialized from a MappedData. This allows data to be inspected without knowing how it was serialized.
+	/// Returns None once every byte has been read
+	pub fn next_tagged(&mut self) -> Option<Result<Datum, DeserializationError>> {
+		if self.remaining_bytes().is_empty() {
+			return None
+		}
+		let datum_type = match self.take_tag() {
+			Ok(x) => x,
+			Err(e) => return Some(Err(e))
+		};
+		Some(self.take_datum(datum_type, DatumSize::U32, 0))
+	}
+	/// Consumes the cursor and returns the buffer it was reading from
+	pub fn into_inner(self) -> B {
+		self.bytes
+	}
+	/// Reads the given number of bytes in place
+	fn take_slice(&mut self, size: usize) -> Result<&[u8], DeserializationError> {
+		let end = self.offset.checked_add(size).ok_or(DeserializationError::UnexpectedEOF)?;
+		let bytes = self.bytes.as_ref().get(self.offset..end).ok_or(DeserializationError::UnexpectedEOF)?;
+		self.offset = end;
+		Ok(bytes)
+	}
+}
+
+
+impl<'a> BinCursor<&'a [u8]> {
+	/// Reads a string item in place, borrowing it from the buffer instead of allocating a String.
+	/// Reads the same bytes as get_datum does for DatumType::String with the given size
+	pub fn next_str(&mut self, datum_size: DatumSize) -> Result<&'a str, DeserializationError> {
+		if self.options.tagged {
+			let datum_type = self.take_tag()?;
+			if datum_type != DatumType::String {
+				return Err(DeserializationError::InvalidType { field: "".into(), expected: DatumType::String.name(), actual: datum_type.name() })
+			}
+		}
+		let size = self.take_size(datum_size)?;
+		let bytes = self.next_slice(size)?;
+		// The error only holds owned bytes, so they are copied only if they are not valid UTF-8
+		std::str::from_utf8(bytes).map_err(|_| String::from_utf8(bytes.to_vec()).unwrap_err().into())
+	}
+	/// Reads the given number of bytes in place, borrowing them from the buffer
+	pub fn next_slice(&mut self, size: usize) -> Result<&'a [u8], DeserializationError> {
+		let bytes: &'a [u8] = self.bytes;
+		let end = self.offset.checked_add(size).ok_or(DeserializationError::UnexpectedEOF)?;
+		let slice = bytes.get(self.offset..end).ok_or(DeserializationError::UnexpectedEOF)?;
+		self.offset = end;
+		Ok(slice)
+	}
+}
+
+
+impl<B: AsRef<[u8]>> ByteSource for BinCursor<B> {
+	fn options(&self) -> ArrayOptions {
+		self.options
+	}
+	fn take(&mut self, size: usize) -> Result<Vec<u8>, DeserializationError> {
+		self.take_slice(size).map(<[u8]>::to_vec)
+	}
+	fn take_arr<const SIZE: usize>(&mut self) -> Result<[u8; SIZE], DeserializationError> {
+		self.take_slice(SIZE)?.try_into().map_err(|_| DeserializationError::UnexpectedEOF)
+	}
+}
+
+
+impl<B: AsRef<[u8]> + Clone + Debug + 'static> DatumArray for BinCursor<B> {
+	fn get_datum(&mut self, datum_type: DatumType, datum_size: DatumSize) -> Result<Datum, DeserializationError> {
+		self.take_item(datum_type, datum_size)
+	}
+	fn set_options(&mut self, options: ArrayOptions) {
+		self.options = options;
+	}
+	fn boxed_clone(&self) -> Box<dyn DatumArray> {
+		Box::new(self.clone())
+	}
+}
+
+
+impl ProfileFromData<Vec<u8>> for ArrayData {
+	fn try_from(data: Vec<u8>) -> Result<Self, DeserializationError> {
+		ProfileFromData::try_from(BinCursor::new(data))
+	}
+}
+
+
+/// Reads binary data from a reader, such as a file or a socket, only as items are deserialized.
+/// Several values can be read one after another from the same BinReader, and the reader can be taken back with into_inner.
+///
+/// A stream cannot be rewound, so clones of a BinReader share the same reader.
+/// Reading from a clone moves every other clone forward as well
+pub struct BinReader<R> {
+	reader: Rc<RefCell<R>>,
+	options: ArrayOptions,
+}
+
+
+impl<R: Read> BinReader<R> {
+	/// Creates a source that reads from the given reader
+	pub fn new(reader: R) -> Self {
+		Self { reader: Rc::new(RefCell::new(reader)), options: ArrayOptions::default() }
+	}
+	/// Returns the reader, with anything that has not been read still in it.
+	/// Returns self instead if a clone of it still exists
+	pub fn into_inner(self) -> Result<R, Self> {
+		let options = self.options;
+		Rc::try_unwrap(self.reader)
+			.map(RefCell::into_inner)
+			.map_err(|reader| Self { reader, options })
+	}
+}
+
+
+impl<R> Clone for BinReader<R> {
+	fn clone(&self) -> Self {
+		Self { reader: self.reader.clone(), options: self.options }
+	}
+}
+
+
+impl<R> Debug for BinReader<R> {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		f.debug_struct("BinReader").finish_non_exhaustive()
+	}
+}
+
+
+impl<R: Read> ByteSource for BinReader<R> {
+	fn options(&self) -> ArrayOptions {
+		self.options
+	}
+	fn take(&mut self, size: usize) -> Result<Vec<u8>, DeserializationError> {
+		// The buffer grows as bytes arrive, so a corrupt length cannot allocate more memory than there is data
+		let mut bytes = Vec::new();
+		self.reader.borrow_mut().deref_mut().take(size as u64).read_to_end(&mut bytes)?;
+		if bytes.len() < size {
+			return Err(DeserializationError::UnexpectedEOF)
+		}
+		Ok(bytes)
+	}
+	fn take_arr<const SIZE: usize>(&mut self) -> Result<[u8; SIZE], DeserializationError> {
+		let mut bytes = [0; SIZE];
+		self.reader.borrow_mut().read_exact(&mut bytes)?;
+		Ok(bytes)
+	}
+}
+
+
+impl<R: Read + 'static> DatumArray for BinReader<R> {
+	fn get_datum(&mut self, datum_type: DatumType, datum_size: DatumSize) -> Result<Datum, DeserializationError> {
+		self.take_item(datum_type, datum_size)
+	}
+	fn set_options(&mut self, options: ArrayOptions) {
+		self.options = options;
+	}
+	fn boxed_clone(&self) -> Box<dyn DatumArray> {
+		Box::new(self.clone())
+	}
+}
+
+
+/// Writes binary data to a writer, such as a file or a socket, as soon as items are serialized.
+/// Several values can be written one after another to the same BinWriter, and the writer can be taken back with into_inner.
+///
+/// Clones of a BinWriter share the same writer
+pub struct BinWriter<W> {
+	writer: Rc<RefCell<W>>,
+}
+
+
+impl<W: Write> BinWriter<W> {
+	/// Creates a destination that writes to the given writer
+	pub fn new(writer: W) -> Self {
+		Self { writer: Rc::new(RefCell::new(writer)) }
+	}
+	/// Returns the writer.
+	/// Returns self instead if a clone of it still exists
+	pub fn into_inner(self) -> Result<W, Self> {
+		Rc::try_unwrap(self.writer)
+			.map(RefCell::into_inner)
+			.map_err(|writer| Self { writer })
+	}
+}
+
+
+impl<W> Clone for BinWriter<W> {
+	fn clone(&self) -> Self {
+		Self { writer: self.writer.clone() }
+	}
+}
+
+
+impl<W> Debug for BinWriter<W> {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		f.debug_struct("BinWriter").finish_non_exhaustive()
+	}
+}
+
+
+/// Writes the items of a single value to a BinWriter as the value is serialized.
+/// Once an item fails to be written, the error is kept and nothing else is written
+struct ItemWriter<W> {
+	writer: Rc<RefCell<W>>,
+	/// The number of items received so far, used to identify items in errors
+	index: usize,
+	/// The bytes of the current item
+	out: Vec<u8>,
+	error: Option<SerializationError>,
+}
+
+
+impl<W> Debug for ItemWriter<W> {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		f.debug_struct("ItemWriter").field("index", &self.index).field("error", &self.error).finish_non_exhaustive()
+	}
+}
+
+
+impl<W: Write> ItemSink for RefCell<ItemWriter<W>> {
+	fn push(&self, item: SerialItem, options: ArrayOptions) {
+		let this = &mut *self.borrow_mut();
+		if this.error.is_some() {
+			return
+		}
+		this.out.clear();
+		if let Err(e) = write_serial_item(&mut this.out, item, options) {
+			this.error = Some(prepend_field(e, &format!("[{}]", this.index)));
+		} else if let Err(e) = this.writer.borrow_mut().write_all(&this.out) {
+			this.error = Some(e.into());
+		}
+		this.index += 1;
+	}
+}
+
+
+/// Writes a datum along with its type tag.
+/// The contents of maps and arrays are written this way, as their types are not known when reading them back
+fn write_tagged(out: &mut Vec<u8>, datum: Datum, options: ArrayOptions) -> Result<(), SerializationError> {
+	out.push(datum.get_type().tag());
+	write_datum(out, datum, DatumSize::U32, options)
+}
+
+
+/// Prepends the given segment to the path of an error from writing binary data
+fn prepend_field(mut error: SerializationError, segment: &str) -> SerializationError {
+	error.prepend_field(segment);
+	error
+}
+
+
+/// Writes the big-endian bytes of a number in the byte order of the options
+fn write_number(out: &mut Vec<u8>, mut bytes: Vec<u8>, options: ArrayOptions) {
+	if options.byte_order == ByteOrder::LittleEndian {
+		bytes.reverse();
+	}
+	out.append(&mut bytes);
+}
+
+
+/// Writes an item of an ArrayData, along with its type tag if tagging is enabled
+fn write_item(out: &mut Vec<u8>, datum: Datum, datum_size: DatumSize, options: ArrayOptions) -> Result<(), SerializationError> {
+	if options.tagged {
+		out.push(datum.get_type().tag());
+	}
+	write_datum(out, datum, datum_size, options)
+}
+
+
+/// Writes an item of an ArrayData.
+/// Returns an error if the item is a count or has a length prefix, and it does not fit in its size
+fn write_serial_item(out: &mut Vec<u8>, item: SerialItem, options: ArrayOptions) -> Result<(), SerializationError> {
+	match item {
+		SerialItem::Datum(datum, datum_size) => write_item(out, datum, datum_size, options),
+		SerialItem::Count(len, size) => write_item(out, count_datum(len, size)?, size, options)
+	}
+}
+
+
+/// Writes a length prefix of the given size.
+/// Returns an error if the length does not fit in the size
+fn write_size(out: &mut Vec<u8>, size: usize, datum_size: DatumSize, options: ArrayOptions) -> Result<(), SerializationError> {
+	if options.varint {
+		write_varint(out, size as u64);
+	} else {
+		write_number(out, datum_size.serialize_usize(size)?, options);
+	}
+	Ok(())
+}
+
+
+/// Writes a datum, prefixing strings with their length, and maps and arrays with their number of entries.
+/// The size of the prefix is given by datum_size.
+/// Returns an error if a prefix does not fit in its size
+fn write_datum(out: &mut Vec<u8>, datum: Datum, datum_size: DatumSize, options: ArrayOptions) -> Result<(), SerializationError> {
+	match datum {
+		Datum::String(x) => {
+			write_size(out, x.len(), datum_size, options)?;
+			out.append(&mut x.into_bytes());
+		}
+		Datum::Map(x) => {
+			let entries = x.into_serialized_entries();
+			write_size(out, entries.len(), datum_size, options)?;
+			for (key, value) in entries {
+				let name = key.to_key_string();
+				write_tagged(out, key, options).map_err(|e| prepend_field(e, &name))?;
+				write_tagged(out, value, options).map_err(|e| prepend_field(e, &name))?;
+			}
+		}
+		Datum::Array(x) => {
+			let items = x.into_items();
+			write_size(out, items.len(), datum_size, options)?;
+			for (index, item) in items.enumerate() {
+				let item = match item {
+					SerialItem::Datum(datum, _) => Ok(datum),
+					SerialItem::Count(len, size) => count_datum(len, size)
+				};
+				item.and_then(|item| write_tagged(out, item, options)).map_err(|e| prepend_field(e, &format!("[{}]", index)))?;
+			}
+		}
+		Datum::U16(x) if options.varint => write_varint(out, x as u64),
+		Datum::U32(x) if options.varint => write_varint(out, x as u64),
+		Datum::U64(x) if options.varint => write_varint(out, x),
+		Datum::I16(x) if options.varint => write_varint(out, zigzag(x as i64)),
+		Datum::I32(x) if options.varint => write_varint(out, zigzag(x as i64)),
+		Datum::I64(x) if options.varint => write_varint(out, zigzag(x)),
+		datum => write_number(out, datum.try_into()?, options)
+	}
+	Ok(())
+}
+
+
+/// Strings are converted without a length prefix, while maps and arrays are prefixed with their number of entries.
+/// Returns an error if a map or array holds a string, map or array longer than u32::MAX
+impl TryFrom<Datum> for Vec<u8> {
+	type Error = SerializationError;
+	
+	fn try_from(data: Datum) -> Result<Self, Self::Error> {
+		Ok(match data {
 			Datum::String(x) => x.into_bytes(),
 			// Datum::Str(x) => String::from(x).into_bytes(),
+			Datum::Bool(x) => vec![x as u8],
+			Datum::U8(x) => vec![x],
+			Datum::U16(x) => x.to_be_bytes().to_vec(),
 			Datum::U64(x) => x.to_be_bytes().to_vec(),
 			Datum::U32(x) => x.to_be_bytes().to_vec(),
-			Datum::Map(_) => todo!(),
-			Datum::Array(x) => ProfileToData::into(x)
-		}
+			Datum::I8(x) => x.to_be_bytes().to_vec(),
+			Datum::I16(x) => x.to_be_bytes().to_vec(),
+			Datum::I32(x) => x.to_be_bytes().to_vec(),
+			Datum::I64(x) => x.to_be_bytes().to_vec(),
+			Datum::F32(x) => x.to_be_bytes().to_vec(),
+			Datum::F64(x) => x.to_be_bytes().to_vec(),
+			datum @ (Datum::Map(_) | Datum::Array(_)) => {
+				let mut out = Vec::new();
+				write_datum(&mut out, datum, DatumSize::U32, ArrayOptions::default()).map_err(SerializationError::globalize)?;
+				out
+			}
+		})
 	}
 }
 
 
+/// If the length of an item does not fit in the size chosen for it, into leaves out that item and every item after it.
+/// Use try_into_data to find out about such items instead
 impl ProfileToData<Vec<u8>> for ArrayData {
 	fn into(self) -> Vec<u8> {
 		let mut out = Vec::new();
-		for (datum, datum_size) in self.into_serialized_items() {
-			match datum.get_type() {
-				DatumType::String => {
-					let mut bytes: Vec<u8> = datum.into();
-					out.append(&mut datum_size.serialize_usize(bytes.len()));
-					out.append(&mut bytes);
-				}
-				_ => out.append(&mut datum.into()),
-			}
-		}
+		let _ = self.write_bin(&mut out);
 		out
 	}
+	fn try_into_data(self) -> Result<Vec<u8>, SerializationError> {
+		let mut out = Vec::new();
+		self.write_bin(&mut out)?;
+		Ok(out)
+	}
+}
+
+
+/// Maps are written as a single tagged map datum, so that the data describes its own types.
+/// If a string, map or array is longer than u32::MAX, into ends the data before it, which leaves the data unreadable.
+/// Use try_into_data to find out about such values instead
+impl ProfileToData<Vec<u8>> for MappedData {
+	fn into(self) -> Vec<u8> {
+		let mut out = Vec::new();
+		let _ = write_tagged(&mut out, Datum::Map(self), ArrayOptions::default());
+		out
+	}
+	fn try_into_data(self) -> Result<Vec<u8>, SerializationError> {
+		let mut out = Vec::new();
+		write_tagged(&mut out, Datum::Map(self), ArrayOptions::default()).map_err(SerializationError::globalize)?;
+		Ok(out)
+	}
+}
+
+
+impl ProfileFromData<Vec<u8>> for MappedData {
+	fn try_from(data: Vec<u8>) -> Result<Self, DeserializationError> {
+		match BinCursor::new(data).take_tagged(0)? {
+			Datum::Map(x) => Ok(x),
+			x => Err(DeserializationError::InvalidType { field: "<global>".into(), expected: "map", actual: x.type_name() })
+		}
+	}
+}
+
+
+impl ArrayData {
+	/// Writes the serialized items to the given writer as binary, one item at a time.
+	/// Returns an error if the length of an item does not fit in the size chosen for it, or if the writer fails
+	pub fn write_bin<W: Write>(self, writer: &mut W) -> Result<(), SerializationError> {
+		let mut out = Vec::new();
+		let options = self.options();
+		for (index, item) in self.into_items().enumerate() {
+			write_serial_item(&mut out, item, options).map_err(|e| prepend_field(e, &format!("[{}]", index)))?;
+			writer.write_all(&out)?;
+			out.clear();
+		}
+		Ok(())
+	}
 }
 
 
 /// Adds explicit methods for converting to and from binary using a given data profile.
 /// Can only be implemented on types that implement Serde with the same data profile
 pub trait BinSerde<T: DataProfile + ProfileToData<Vec<u8>> + ProfileFromData<Vec<u8>>>: Serde<T> {
-	/// Serializes self into binary
+	/// Serializes self into binary.
+	/// If serde returns an error, or if the length of an item does not fit in the size chosen for it,
+	/// the binary ends before the item. Use try_serialize_bin to get the error instead
 	fn serialize_bin(self) -> Vec<u8> {
 		self.serialize()
 	}
+	/// Serializes self into binary.
+	/// Returns an error if serde returns an error, or if the length of an item does not fit in the size chosen for it
+	fn try_serialize_bin(self) -> Result<Vec<u8>, SerializationError> {
+		self.try_serialize()
+	}
+	/// Serializes self into binary, writing every item to the given writer, such as a file or a socket, as soon as it is serialized.
+	/// The items of a nested value are written once the nested value is complete, and a byte of packed booleans once it is full.
+	/// The writer is owned by the data profile while serializing, which is why it must be 'static.
+	///
+	/// Returns an error if serde returns an error, if the length of an item does not fit in the size chosen for it, or if the writer fails.
+	/// The items before the error may already have been written
+	fn serialize_bin_to<W: Write + 'static>(mut self, writer: &BinWriter<W>) -> Result<(), SerializationError> where T: DerefMut<Target=ArrayData> {
+		let sink = Rc::new(RefCell::new(ItemWriter { writer: writer.writer.clone(), index: 0, out: Vec::new(), error: None }));
+		let mut data = T::serial_ready();
+		data.set_sink(sink.clone());
+		serde_value(&mut self, &mut data)?;
+		data.close_sink();
+		let error = sink.borrow_mut().error.take();
+		error.map_or(Ok(()), Err)
+	}
+	/// Serializes self into binary, leaving self intact.
+	/// If the length of an item does not fit in the size chosen for it, the binary ends before the item
+	fn serialize_bin_ref(&self) -> Vec<u8> where Self: SerializeRef<T> {
+		self.serialize_ref()
+	}
 	/// Deserializes a binary vector into Self.
 	/// Returns an error if the string could not be deserialized
-	fn deserialize_bin(data: Vec<u8>) -> Result<Self, DeserializationError> {
+	fn deserialize_bin(data: Vec<u8>) -> Result<Self, DeserializationError> where Self: Default {
 		Self::deserialize(data)
 	}
+	/// Deserializes any buffer of bytes into Self, such as a &'static [u8] or a reference counted buffer.
+	/// The buffer is read in place, without being copied, but strings are still copied into the Strings of Self.
+	/// See BinCursor::next_str to borrow strings from the buffer instead
+	fn deserialize_bin_buffer<B>(data: B) -> Result<Self, DeserializationError>
+		where
+			Self: Default,
+			B: AsRef<[u8]>,
+			T: ProfileFromData<BinCursor<B>>
+	{
+		Self::deserialize(BinCursor::new(data))
+	}
+	/// Deserializes Self from the given reader, such as a file or a socket.
+	/// Bytes are only read as items are deserialized, so anything after the data is left in the reader,
+	/// where the next value can be deserialized from
+	fn deserialize_bin_from<R: Read + 'static>(reader: &BinReader<R>) -> Result<Self, DeserializationError>
+		where
+			Self: Default,
+			T: ProfileFromData<BinReader<R>>
+	{
+		Self::deserialize(reader.clone())
+	}
+	/// Deserializes a binary vector into self, updating the existing value.
+	/// Returns an error if the binary could not be deserialized
+	fn deserialize_bin_into(&mut self, data: Vec<u8>) -> Result<(), DeserializationError> {
+		self.deserialize_into(data)
+	}
+	/// Constructs Self from a binary vector.
+	/// Returns an error if the binary could not be deserialized
+	fn deserialize_bin_new(data: Vec<u8>) -> Result<Self, DeserializationError> where Self: DeserializeNew<T> {
+		Self::deserialize_new(data)
+	}
 }
@@ -1,6 +1,8 @@
-use std::collections::HashMap;
+use std::any::type_name;
+use std::collections::{HashMap, VecDeque};
 use std::hash::{Hash, Hasher};
-use crate::profiles::{DatumMap, MappedData};
+use std::str::FromStr;
+use crate::profiles::{DatumArray, DatumMap, MappedData};
 
 use super::*;
 
@@ -17,8 +19,17 @@ pub trait Equals<T> {
 pub enum Datum {
 	String(String),
 	// Str(&'static str),
+	Bool(bool),
+	U8(u8),
+	U16(u16),
 	U32(u32),
 	U64(u64),
+	I8(i8),
+	I16(i16),
+	I32(i32),
+	I64(i64),
+	F32(f32),
+	F64(f64),
 	Map(MappedData),
 	Array(ArrayData)
 }
@@ -40,14 +51,50 @@ impl PartialEq for Datum {
 			// Datum::Str(x) => 	if let Datum::Str(o) = other {
 			// 								x == o
 			// 							} else { false }
+			Datum::Bool(x) => 	if let Datum::Bool(o) = other {
+									x == o
+								} else { false }
+			Datum::U8(x) => 	if let Datum::U8(o) = other {
+									x == o
+								} else { false }
+			Datum::U16(x) => 	if let Datum::U16(o) = other {
+									x == o
+								} else { false }
 			Datum::U32(x) =>  if let Datum::U32(o) = other {
 										x == o
 									} else { false }
 			Datum::U64(x) => 	if let Datum::U64(o) = other {
 									x == o
 								} else { false }
-			Datum::Map(_) => unimplemented!("Cannot compare MappedData"),
-			Datum::Array(_) => unimplemented!("Cannot compare ArrayData")
+			Datum::I8(x) => 	if let Datum::I8(o) = other {
+									x == o
+								} else { false }
+			Datum::I16(x) => 	if let Datum::I16(o) = other {
+									x == o
+								} else { false }
+			Datum::I32(x) => 	if let Datum::I32(o) = other {
+									x == o
+								} else { false }
+			Datum::I64(x) => 	if let Datum::I64(o) = other {
+									x == o
+								} else { false }
+			// Floats are compared bitwise so that Eq and Hash stay consistent
+			Datum::F32(x) => 	if let Datum::F32(o) = other {
+									x.to_bits() == o.to_bits()
+								} else { false }
+			Datum::F64(x) => 	if let Datum::F64(o) = other {
+									x.to_bits() == o.to_bits()
+								} else { false }
+			Datum::Map(x) => 	if let Datum::Map(o) = other {
+									x.entries() == o.entries()
+								} else { false }
+			// Arrays whose items cannot be listed, such as arrays read from binary, are only equal to themselves
+			Datum::Array(x) => 	if let Datum::Array(o) = other {
+									match (x.items(), o.items()) {
+										(None, None) => std::ptr::eq(x, o),
+										(items, other_items) => items == other_items
+									}
+								} else { false }
 		}
 	}
 }
@@ -61,10 +108,19 @@ impl Clone for Datum {
 		match self {
 			Datum::String(x) => Datum::String(x.clone()),
 			// Datum::Str(x) => Datum::Str(x.clone()),
+			Datum::Bool(x) => Datum::Bool(*x),
+			Datum::U8(x) => Datum::U8(*x),
+			Datum::U16(x) => Datum::U16(*x),
 			Datum::U32(x) => Datum::U32(*x),
 			Datum::U64(x) => Datum::U64(*x),
-			Datum::Map(_) => unimplemented!("Cannot clone a MappedData"),
-			Datum::Array(_) => unimplemented!("Cannot clone an ArrayData")
+			Datum::I8(x) => Datum::I8(*x),
+			Datum::I16(x) => Datum::I16(*x),
+			Datum::I32(x) => Datum::I32(*x),
+			Datum::I64(x) => Datum::I64(*x),
+			Datum::F32(x) => Datum::F32(*x),
+			Datum::F64(x) => Datum::F64(*x),
+			Datum::Map(x) => Datum::Map(x.clone()),
+			Datum::Array(x) => Datum::Array(x.clone())
 		}
 	}
 }
@@ -75,20 +131,39 @@ impl Hash for Datum {
 		match self {
 			Datum::String(x) => x.hash(state),
 			// Datum::Str(x) => x.hash(state),
+			Datum::Bool(x) => x.hash(state),
+			Datum::U8(x) => x.hash(state),
+			Datum::U16(x) => x.hash(state),
 			Datum::U32(x) => x.hash(state),
 			Datum::U64(x) => x.hash(state),
-			Datum::Map(_) => unimplemented!("Cannot hash MappedData!"),
-			Datum::Array(_) => unimplemented!("Cannot hash ArrayData!")
+			Datum::I8(x) => x.hash(state),
+			Datum::I16(x) => x.hash(state),
+			Datum::I32(x) => x.hash(state),
+			Datum::I64(x) => x.hash(state),
+			Datum::F32(x) => x.to_bits().hash(state),
+			Datum::F64(x) => x.to_bits().hash(state),
+			// Entries have no order, so only their count is hashed. This is consistent with PartialEq
+			Datum::Map(x) => x.entries().len().hash(state),
+			Datum::Array(x) => x.items().hash(state)
 		}
 	}
 }
 
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum DatumType {
 	String,
+	Bool,
+	U8,
+	U16,
 	U32,
 	U64,
+	I8,
+	I16,
+	I32,
+	I64,
+	F32,
+	F64,
 	Map,
 	Array
 }
@@ -103,7 +178,65 @@ pub enum DatumSize {
 }
 
 
+impl DatumType {
+	/// Returns the name of the type, as used in error messages
+	pub fn name(self) -> &'static str {
+		match self {
+			DatumType::String => "String",
+			DatumType::Bool => "bool",
+			DatumType::U8 => "u8",
+			DatumType::U16 => "u16",
+			DatumType::U32 => "u32",
+			DatumType::U64 => "u64",
+			DatumType::I8 => "i8",
+			DatumType::I16 => "i16",
+			DatumType::I32 => "i32",
+			DatumType::I64 => "i64",
+			DatumType::F32 => "f32",
+			DatumType::F64 => "f64",
+			DatumType::Map => "map",
+			DatumType::Array => "array"
+		}
+	}
+	#[cfg(feature = "bin")]
+	/// Returns the byte that identifies the type in binary data that describes its own types
+	pub(crate) fn tag(self) -> u8 {
+		self as u8
+	}
+	#[cfg(feature = "bin")]
+	/// Returns the type identified by the given byte, if there is one
+	pub(crate) fn from_tag(tag: u8) -> Option<Self> {
+		Some(match tag {
+			0 => DatumType::String,
+			1 => DatumType::Bool,
+			2 => DatumType::U8,
+			3 => DatumType::U16,
+			4 => DatumType::U32,
+			5 => DatumType::U64,
+			6 => DatumType::I8,
+			7 => DatumType::I16,
+			8 => DatumType::I32,
+			9 => DatumType::I64,
+			10 => DatumType::F32,
+			11 => DatumType::F64,
+			12 => DatumType::Map,
+			13 => DatumType::Array,
+			_ => return None
+		})
+	}
+}
+
+
 impl DatumSize {
+	/// Returns the largest length that can be written with this size
+	pub fn max_len(self) -> usize {
+		match self {
+			DatumSize::U8 => u8::MAX as usize,
+			DatumSize::U16 => u16::MAX as usize,
+			DatumSize::U32 => u32::MAX as usize,
+			DatumSize::U64 => u64::MAX as usize
+		}
+	}
 	pub fn into_byte_size(self) -> usize {
 		match self {
 			DatumSize::U8 => 1,
@@ -113,13 +246,16 @@ impl DatumSize {
 		}
 	}
 
-	pub fn serialize_usize(self, size: usize) -> Vec<u8> {
-		match self {
-			DatumSize::U8 => vec![size as u8],
-			DatumSize::U16 => (size as u16).to_be_bytes().to_vec(),
-			DatumSize::U32 => (size as u32).to_be_bytes().to_vec(),
-			DatumSize::U64 => (size as u64).to_be_bytes().to_vec()
-		}
+	/// Returns the big-endian bytes of a length written with this size.
+	/// Returns an error if the length does not fit
+	pub fn serialize_usize(self, size: usize) -> Result<Vec<u8>, SerializationError> {
+		let bytes = match self {
+			DatumSize::U8 => u8::try_from(size).map(|n| n.to_be_bytes().to_vec()),
+			DatumSize::U16 => u16::try_from(size).map(|n| n.to_be_bytes().to_vec()),
+			DatumSize::U32 => u32::try_from(size).map(|n| n.to_be_bytes().to_vec()),
+			DatumSize::U64 => u64::try_from(size).map(|n| n.to_be_bytes().to_vec())
+		};
+		bytes.map_err(|_| SerializationError::LengthOverflow { field: "".into(), length: size, size: self })
 	}
 }
 
@@ -129,15 +265,77 @@ impl Datum {
 		match self {
 			Self::String(_) => DatumType::String,
 			// Self::Str(_) => DatumType::Str,
+			Self::Bool(_) => DatumType::Bool,
+			Self::U8(_) => DatumType::U8,
+			Self::U16(_) => DatumType::U16,
 			Self::U32(_) => DatumType::U32,
 			Self::U64(_) => DatumType::U64,
+			Self::I8(_) => DatumType::I8,
+			Self::I16(_) => DatumType::I16,
+			Self::I32(_) => DatumType::I32,
+			Self::I64(_) => DatumType::I64,
+			Self::F32(_) => DatumType::F32,
+			Self::F64(_) => DatumType::F64,
 			Self::Map(_) => DatumType::Map,
 			Self::Array(_) => DatumType::Array
 		}
 	}
+	/// Returns the name of the type of this datum, as used in error messages
+	pub fn type_name(&self) -> &'static str {
+		self.get_type().name()
+	}
+	/// Returns the value of an integer datum, regardless of its width or sign
+	fn as_integer(&self) -> Option<i128> {
+		Some(match self {
+			Datum::U8(n) => *n as i128,
+			Datum::U16(n) => *n as i128,
+			Datum::U32(n) => *n as i128,
+			Datum::U64(n) => *n as i128,
+			Datum::I8(n) => *n as i128,
+			Datum::I16(n) => *n as i128,
+			Datum::I32(n) => *n as i128,
+			Datum::I64(n) => *n as i128,
+			_ => return None
+		})
+	}
+	/// Returns the value of a float datum.
+	/// Integers are also accepted, as human written formats often omit the decimal point,
+	/// and so are the strings that formats without non-finite numbers use in their place
+	fn as_float(&self) -> Option<f64> {
+		Some(match self {
+			Datum::F32(n) => *n as f64,
+			Datum::F64(n) => *n,
+			Datum::String(s) => match s.as_str() {
+				NAN_STRING => f64::NAN,
+				INFINITY_STRING => f64::INFINITY,
+				NEG_INFINITY_STRING => f64::NEG_INFINITY,
+				_ => return None
+			},
+			x => x.as_integer()? as f64
+		})
+	}
 }
 
 
+/// Widens an f32 into the f64 with the shortest decimal form that rounds back to it,
+/// so that text formats write an f32 of 0.1 as 0.1 rather than 0.10000000149011612
+#[cfg(any(feature = "toml", feature = "json"))]
+pub(crate) fn widen_f32(n: f32) -> f64 {
+	if !n.is_finite() {
+		return n as f64
+	}
+	n.to_string().parse().unwrap_or(n as f64)
+}
+
+
+/// The string that represents NaN in formats that cannot express it
+pub(crate) const NAN_STRING: &str = "NaN";
+/// The string that represents positive infinity in formats that cannot express it
+pub(crate) const INFINITY_STRING: &str = "Infinity";
+/// The string that represents negative infinity in formats that cannot express it
+pub(crate) const NEG_INFINITY_STRING: &str = "-Infinity";
+
+
 impl PartialEq<Datum> for &str {
 	fn eq(&self, other: &Datum) -> bool {
 		match other {
@@ -163,6 +361,27 @@ impl From<&str> for Datum {
 }
 
 
+impl From<bool> for Datum {
+	fn from(b: bool) -> Self {
+		Self::Bool(b)
+	}
+}
+
+
+impl From<u8> for Datum {
+	fn from(n: u8) -> Self {
+		Self::U8(n)
+	}
+}
+
+
+impl From<u16> for Datum {
+	fn from(n: u16) -> Self {
+		Self::U16(n)
+	}
+}
+
+
 impl From<u64> for Datum {
 	fn from(n: u64) -> Self {
 		Self::U64(n)
@@ -177,6 +396,48 @@ impl From<u32> for Datum {
 }
 
 
+impl From<i8> for Datum {
+	fn from(n: i8) -> Self {
+		Self::I8(n)
+	}
+}
+
+
+impl From<i16> for Datum {
+	fn from(n: i16) -> Self {
+		Self::I16(n)
+	}
+}
+
+
+impl From<i32> for Datum {
+	fn from(n: i32) -> Self {
+		Self::I32(n)
+	}
+}
+
+
+impl From<i64> for Datum {
+	fn from(n: i64) -> Self {
+		Self::I64(n)
+	}
+}
+
+
+impl From<f32> for Datum {
+	fn from(n: f32) -> Self {
+		Self::F32(n)
+	}
+}
+
+
+impl From<f64> for Datum {
+	fn from(n: f64) -> Self {
+		Self::F64(n)
+	}
+}
+
+
 impl From<usize> for Datum {
 	fn from(n: usize) -> Self {
 		Self::U64(n as u64)
@@ -184,10 +445,32 @@ impl From<usize> for Datum {
 }
 
 
+impl From<&String> for Datum {
+	fn from(s: &String) -> Self {
+		Self::String(s.clone())
+	}
+}
+
+
+/// Implements From<&T> for Datum on copyable types,
+/// so that fields can be serialized by reference
+macro_rules! impl_from_ref {
+	($($t: ty),*) => {$(
+impl From<&$t> for Datum {
+	fn from(n: &$t) -> Self {
+		(*n).into()
+	}
+}
+	)*};
+}
+
+impl_from_ref!(bool, u8, u16, u32, u64, i8, i16, i32, i64, f32, f64, usize);
+
+
 impl<E, K, V> DatumMap for HashMap<K, V>
 	where
-		K: TryFrom<Datum, Error=E> + Debug + Eq + Hash,
-		V: Into<Datum> + Debug,
+		K: TryFrom<Datum, Error=E> + Into<Datum> + Clone + Debug + Eq + Hash + 'static,
+		V: Into<Datum> + Clone + Debug + 'static,
 		DeserializationError: From<E>
 {
 	fn get_datum(&mut self, key: &Datum) -> Result<Datum, DeserializationError> {
@@ -196,6 +479,43 @@ impl<E, K, V> DatumMap for HashMap<K, V>
 			None => Err(DeserializationError::MissingField(key.to_key_string()))
 		}
 	}
+	fn remaining_keys(&self) -> Vec<Datum> {
+		self.keys().cloned().map(Into::into).collect()
+	}
+	fn boxed_clone(&self) -> Box<dyn DatumMap> {
+		Box::new(self.clone())
+	}
+}
+
+
+/// Converts a key datum back into the key type of a map.
+/// Text formats store every key as a string, so string keys are parsed instead of converted
+pub(crate) fn parse_key<K, E>(key: Datum) -> Result<K, DeserializationError>
+	where
+		K: TryFrom<Datum, Error=E> + FromStr,
+		DeserializationError: From<E>
+{
+	match key {
+		Datum::String(s) => K::from_str(&s).map_err(|_| DeserializationError::InvalidType {
+			field: s,
+			expected: type_name::<K>(),
+			actual: "string"
+		}),
+		key => Ok(K::try_from(key)?)
+	}
+}
+
+
+impl DatumArray for VecDeque<Datum> {
+	fn get_datum(&mut self, _datum_type: DatumType, _datum_size: DatumSize) -> Result<Datum, DeserializationError> {
+		self.pop_front().ok_or(DeserializationError::UnexpectedEOF)
+	}
+	fn remaining(&self) -> Option<usize> {
+		Some(self.len())
+	}
+	fn boxed_clone(&self) -> Box<dyn DatumArray> {
+		Box::new(self.clone())
+	}
 }
 
 
@@ -207,38 +527,59 @@ impl<E, K, V> DatumMap for HashMap<K, V>
 // }
 
 
-impl TryFrom<Datum> for usize {
+/// Implements TryFrom<Datum> for integer types.
+/// Any integer datum is accepted as long as its value fits in the target type,
+/// as text formats do not preserve the width of integers
+macro_rules! impl_try_from_integer {
+	($($int: ty),*) => {$(
+impl TryFrom<Datum> for $int {
 	type Error = DeserializationError;
-	
+
 	fn try_from(value: Datum) -> Result<Self, Self::Error> {
-		match value {
-			Datum::U64(n) => Ok(n as usize),
-			_ => Err(DeserializationError::InvalidType { field: "".into(), expected: "u64", actual: "todo!" })
-		}
+		value.as_integer()
+			.and_then(|n| <$int>::try_from(n).ok())
+			.ok_or_else(|| DeserializationError::InvalidType { field: "".into(), expected: stringify!($int), actual: value.type_name() })
 	}
 }
+	)*};
+}
+
+impl_try_from_integer!(usize, u8, u16, u32, u64, i8, i16, i32, i64);
 
 
-impl TryFrom<Datum> for u32 {
+impl TryFrom<Datum> for bool {
 	type Error = DeserializationError;
 
 	fn try_from(value: Datum) -> Result<Self, Self::Error> {
 		match value {
-			Datum::U32(n) => Ok(n),
-			_ => Err(DeserializationError::InvalidType { field: "".into(), expected: "u32", actual: "todo!" })
+			Datum::Bool(b) => Ok(b),
+			x => Err(DeserializationError::InvalidType { field: "".into(), expected: "bool", actual: x.type_name() })
 		}
 	}
 }
 
 
-impl TryFrom<Datum> for u64 {
+/// Values are rounded to the nearest f32, as text formats write every float as an f64.
+/// Finite values that are too large for an f32 are rejected rather than turned into infinity
+impl TryFrom<Datum> for f32 {
 	type Error = DeserializationError;
 
 	fn try_from(value: Datum) -> Result<Self, Self::Error> {
-		match value {
-			Datum::U64(n) => Ok(n),
-			_ => Err(DeserializationError::InvalidType { field: "".into(), expected: "u64", actual: "todo!" })
-		}
+		value.as_float()
+			.map(|n| (n, n as f32))
+			.filter(|(n, narrowed)| narrowed.is_finite() || !n.is_finite())
+			.map(|(_, narrowed)| narrowed)
+			.ok_or_else(|| DeserializationError::InvalidType { field: "".into(), expected: "f32", actual: value.type_name() })
+	}
+}
+
+
+impl TryFrom<Datum> for f64 {
+	type Error = DeserializationError;
+
+	fn try_from(value: Datum) -> Result<Self, Self::Error> {
+		value.as_float()
+			.ok_or_else(|| DeserializationError::InvalidType { field: "".into(), expected: "f64", actual: value.type_name() })
 	}
 }
 
@@ -249,7 +590,7 @@ impl TryFrom<Datum> for String {
 	fn try_from(value: Datum) -> Result<Self, Self::Error> {
 		match value {
 			Datum::String(x) => Ok(x),
-			_ => Err(DeserializationError::InvalidType { field: "".into(), expected: "String", actual: "todo!" })
+			x => Err(DeserializationError::InvalidType { field: "".into(), expected: "String", actual: x.type_name() })
 		}
 	}
 }
@@ -260,6 +601,20 @@ pub trait GetDatumType {
 }
 
 
+impl GetDatumType for MappedData {
+	fn get_datum_type() -> DatumType {
+		DatumType::Map
+	}
+}
+
+
+impl GetDatumType for ArrayData {
+	fn get_datum_type() -> DatumType {
+		DatumType::Array
+	}
+}
+
+
 impl GetDatumType for String {
 	fn get_datum_type() -> DatumType {
 		DatumType::String
@@ -274,6 +629,34 @@ impl GetDatumType for &str {
 }
 
 
+impl GetDatumType for bool {
+	fn get_datum_type() -> DatumType {
+		DatumType::Bool
+	}
+}
+
+
+impl GetDatumType for u8 {
+	fn get_datum_type() -> DatumType {
+		DatumType::U8
+	}
+}
+
+
+impl GetDatumType for u16 {
+	fn get_datum_type() -> DatumType {
+		DatumType::U16
+	}
+}
+
+
+impl GetDatumType for u32 {
+	fn get_datum_type() -> DatumType {
+		DatumType::U32
+	}
+}
+
+
 impl GetDatumType for u64 {
 	fn get_datum_type() -> DatumType {
 		DatumType::U64
@@ -281,16 +664,76 @@ impl GetDatumType for u64 {
 }
 
 
-#[cfg(any(feature = "toml", feature = "json"))]
+impl GetDatumType for i8 {
+	fn get_datum_type() -> DatumType {
+		DatumType::I8
+	}
+}
+
+
+impl GetDatumType for i16 {
+	fn get_datum_type() -> DatumType {
+		DatumType::I16
+	}
+}
+
+
+impl GetDatumType for i32 {
+	fn get_datum_type() -> DatumType {
+		DatumType::I32
+	}
+}
+
+
+impl GetDatumType for i64 {
+	fn get_datum_type() -> DatumType {
+		DatumType::I64
+	}
+}
+
+
+impl GetDatumType for f32 {
+	fn get_datum_type() -> DatumType {
+		DatumType::F32
+	}
+}
+
+
+impl GetDatumType for f64 {
+	fn get_datum_type() -> DatumType {
+		DatumType::F64
+	}
+}
+
+
 impl Datum {
 	pub fn to_key_string(&self) -> String {
 		match self {
 			Datum::String(s) => s.clone(),
 			// Datum::Str(s) => (*s).into(),
+			Datum::Bool(s) => s.to_string(),
+			Datum::U8(s) => s.to_string(),
+			Datum::U16(s) => s.to_string(),
 			Datum::U32(s) => s.to_string(),
 			Datum::U64(s) => s.to_string(),
-			Datum::Map(_) => unimplemented!("Cannot turn map into key string"),
-			Datum::Array(_) => unimplemented!("Cannot turn array into key string")
+			Datum::I8(s) => s.to_string(),
+			Datum::I16(s) => s.to_string(),
+			Datum::I32(s) => s.to_string(),
+			Datum::I64(s) => s.to_string(),
+			Datum::F32(s) => s.to_string(),
+			Datum::F64(s) => s.to_string(),
+			Datum::Map(x) => {
+				let mut entries: Vec<String> = x.entries()
+					.into_iter()
+					.map(|(key, value)| format!("{} = {}", key.to_key_string(), value.to_key_string()))
+					.collect();
+				entries.sort();
+				format!("{{{}}}", entries.join(", "))
+			}
+			Datum::Array(x) => match x.items() {
+				Some(items) => format!("[{}]", items.iter().map(Datum::to_key_string).collect::<Vec<_>>().join(", ")),
+				None => "[..]".into()
+			}
 		}
 	}
 }
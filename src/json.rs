@@ -1,8 +1,10 @@
 use std::borrow::Borrow;
+use std::collections::VecDeque;
+use std::io::{Read, Write};
 use extern_json::{Error, JsonValue as Value, object::Object as Table, Array};
 
-use crate::datum::Datum;
-use crate::{ArrayData, DataProfile, DeserializationError, Serde};
+use crate::datum::{Datum, INFINITY_STRING, NAN_STRING, NEG_INFINITY_STRING, widen_f32};
+use crate::{ArrayData, DataProfile, DeserializationError, DeserializeNew, Serde, SerializationError, SerializeRef, TransformResult};
 use crate::profiles::{DatumMap, MappedData};
 use crate::profiles::{ProfileFromData, ProfileToData};
 
@@ -10,10 +12,20 @@ impl DatumMap for Table {
 	fn get_datum(&mut self, key: &Datum) -> Result<Datum, DeserializationError> {
 		let key_string = key.to_key_string();
 		match self.remove(key_string.as_str()) {
-			None => Err(DeserializationError::MissingField(key_string)),
-			Some(x) => Datum::try_from(x)
+			// Null entries are treated as missing, so that they can be read into optional entries
+			None | Some(Value::Null) => Err(DeserializationError::MissingField(key_string)),
+			Some(x) => Datum::try_from(x).transform(key_string)
 		}
 	}
+	fn remaining_keys(&self) -> Vec<Datum> {
+		self.iter()
+			.filter(|(_, value)| !value.is_null())
+			.map(|(key, _)| Datum::from(key))
+			.collect()
+	}
+	fn boxed_clone(&self) -> Box<dyn DatumMap> {
+		Box::new(self.clone())
+	}
 }
 
 
@@ -54,7 +66,7 @@ impl ProfileFromData<Value> for MappedData {
 	fn try_from(data: Value) -> Result<Self, DeserializationError> {
 		let table = match data {
 			Value::Object(x) => x,
-			_ => return Err(DeserializationError::InvalidType { field: "<global>".into(), expected: "table", actual: "todo!" })
+			x => return Err(DeserializationError::InvalidType { field: "<global>".into(), expected: "table", actual: type_str(&x) })
 		};
 		
 		ProfileFromData::try_from(table)
@@ -62,19 +74,34 @@ impl ProfileFromData<Value> for MappedData {
 }
 
 
+/// Returns the name of the type of a JSON value, as used in error messages
+fn type_str(value: &Value) -> &'static str {
+	match value {
+		Value::Null => "null",
+		Value::Short(_) | Value::String(_) => "string",
+		Value::Number(_) => "number",
+		Value::Boolean(_) => "boolean",
+		Value::Object(_) => "object",
+		Value::Array(_) => "array"
+	}
+}
+
+
 impl TryFrom<Value> for Datum {
 	type Error = DeserializationError;
 	
 	fn try_from(value: Value) -> Result<Self, Self::Error> {
-		if value.is_object() {
-			return Ok(Self::Map(ProfileFromData::try_from(value)?));
-		}
-
 		if value.is_number() {
-			return Ok(Self::U64(
-				value.as_u64().ok_or(DeserializationError::InvalidType {
+			if let Some(n) = value.as_u64() {
+				return Ok(Self::U64(n));
+			}
+			if let Some(n) = value.as_i64() {
+				return Ok(Self::I64(n));
+			}
+			return Ok(Self::F64(
+				value.as_f64().ok_or(DeserializationError::InvalidType {
 					field: "".into(),
-					expected: "integer",
+					expected: "number",
 					actual: "nan"
 				})?
 			));
@@ -83,19 +110,53 @@ impl TryFrom<Value> for Datum {
 		Ok(match value {
 			Value::String(s) => Self::String(s),
 			Value::Short(s) => Self::String(s.into()),
-			Value::Number(_) => unreachable!(),
-			Value::Object(_) => unreachable!(),
-			x => todo!("{:?}", x)
+			Value::Boolean(b) => Self::Bool(b),
+			Value::Array(values) => Self::Array(ProfileFromData::try_from(
+				values.into_iter()
+					.enumerate()
+					.map(|(index, value)| Datum::try_from(value).transform(format!("[{}]", index)))
+					.collect::<Result<VecDeque<_>, _>>()?
+			)?),
+			Value::Object(_) => Self::Map(ProfileFromData::try_from(value)?),
+			x => return Err(DeserializationError::InvalidType {
+				field: "".into(),
+				expected: "value",
+				actual: type_str(&x)
+			})
 		})
 	}
 }
 
 
+/// JSON cannot express NaN or infinity, so they are written as strings instead.
+/// Float fields accept these strings when deserializing
+fn float_to_value(n: f64) -> Value {
+	if n.is_nan() {
+		NAN_STRING.into()
+	} else if n == f64::INFINITY {
+		INFINITY_STRING.into()
+	} else if n == f64::NEG_INFINITY {
+		NEG_INFINITY_STRING.into()
+	} else {
+		n.into()
+	}
+}
+
+
 impl From<Datum> for Value {
 	fn from(datum: Datum) -> Self {
 		match datum {
-			Datum::U64(n) => (n as i64).into(),
-			Datum::U32(n) => (n as i64).into(),
+			Datum::U64(n) => n.into(),
+			Datum::U32(n) => n.into(),
+			Datum::Bool(b) => b.into(),
+			Datum::U8(n) => n.into(),
+			Datum::U16(n) => n.into(),
+			Datum::I8(n) => n.into(),
+			Datum::I16(n) => n.into(),
+			Datum::I32(n) => n.into(),
+			Datum::I64(n) => n.into(),
+			Datum::F32(n) => float_to_value(widen_f32(n)),
+			Datum::F64(n) => float_to_value(n),
 			Datum::String(s) => s.into(),
 			Datum::Map(map) => ProfileToData::into(map),
 			// Datum::Str(s) => s.into(),
@@ -114,9 +175,51 @@ pub trait JSONSerde<T: DataProfile + ProfileToData<Value> + ProfileFromData<Valu
 	fn serialize_json_pretty(self) -> String {
 		extern_json::stringify_pretty(self.serialize::<Value>(), Self::TAB_SIZE)
 	}
+	/// Serializes self into a JSON formatted string.
+	/// Returns an error if serde returns an error
+	fn try_serialize_json(self) -> Result<String, SerializationError> {
+		Ok(extern_json::stringify(self.try_serialize::<Value>()?))
+	}
+	/// Serializes self into JSON, writing it to the given writer, such as a file or a socket.
+	/// Returns an error if serde returns an error, or if the writer fails
+	fn serialize_json_to<W: Write>(self, mut writer: W) -> Result<(), SerializationError> {
+		self.try_serialize::<Value>()?.write(&mut writer)?;
+		Ok(())
+	}
+	/// Serializes self into indented JSON, writing it to the given writer, such as a file or a socket.
+	/// Returns an error if serde returns an error, or if the writer fails
+	fn serialize_json_pretty_to<W: Write>(self, mut writer: W) -> Result<(), SerializationError> {
+		self.try_serialize::<Value>()?.write_pretty(&mut writer, Self::TAB_SIZE)?;
+		Ok(())
+	}
+	/// Serializes self into a JSON formatted string, leaving self intact
+	fn serialize_json_ref(&self) -> String where Self: SerializeRef<T> {
+		extern_json::stringify(self.serialize_ref::<Value>())
+	}
+	/// Serializes self into an indented JSON formatted string, leaving self intact
+	fn serialize_json_pretty_ref(&self) -> String where Self: SerializeRef<T> {
+		extern_json::stringify_pretty(self.serialize_ref::<Value>(), Self::TAB_SIZE)
+	}
 	/// Deserializes a string type into Self.
 	/// Returns an error if the string could not be deserialized
-	fn deserialize_json<S: Borrow<str>>(data: S) -> Result<Self, DeserializationError> {
+	fn deserialize_json<S: Borrow<str>>(data: S) -> Result<Self, DeserializationError> where Self: Default {
 		Self::deserialize::<Value>(extern_json::parse(data.borrow())?)
 	}
+	/// Deserializes Self from JSON read from the given reader, such as a file or a socket.
+	/// The reader is read to the end before deserializing
+	fn deserialize_json_from<R: Read>(mut reader: R) -> Result<Self, DeserializationError> where Self: Default {
+		let mut data = String::new();
+		reader.read_to_string(&mut data)?;
+		Self::deserialize_json(data)
+	}
+	/// Deserializes a string type into self, updating the existing value.
+	/// Returns an error if the string could not be deserialized
+	fn deserialize_json_into<S: Borrow<str>>(&mut self, data: S) -> Result<(), DeserializationError> {
+		self.deserialize_into::<Value>(extern_json::parse(data.borrow())?)
+	}
+	/// Constructs Self from a string type.
+	/// Returns an error if the string could not be deserialized
+	fn deserialize_json_new<S: Borrow<str>>(data: S) -> Result<Self, DeserializationError> where Self: DeserializeNew<T> {
+		Self::deserialize_new::<Value>(extern_json::parse(data.borrow())?)
+	}
 }
@@ -20,22 +20,34 @@
 // #![deny(missing_docs, missing_debug_implementations, missing_crate_level_docs, missing_fragment_specifier, missing_copy_implementations)]
 #![forbid(unsafe_code, unconditional_panic)]
 
+// Allows the paths generated by the derive macro to be used inside this crate
+extern crate self as mangle_serde;
 #[cfg(feature = "toml")]
 extern crate toml as extern_toml;
 #[cfg(feature = "json")]
 extern crate json as extern_json;
 
-use std::fmt::Debug;
+use std::convert::Infallible;
+use std::error::Error;
+use std::fmt::{Debug, Display, Formatter};
 use std::ops::{Deref, DerefMut};
 use std::string::FromUtf8Error;
 
 #[cfg(feature = "toml")]
-use extern_toml::{de::Error as TOMLError};
+use extern_toml::{de::Error as TOMLError, ser::Error as TOMLSerError};
 #[cfg(feature = "json")]
 use extern_json::{Error as JSONError};
 
-use crate::profiles::DataProfile;
-pub use crate::profiles::{ArrayData, MappedData, ProfileFromData, ProfileToData};
+pub use crate::datum::{Datum, DatumSize, DatumType, Equals, GetDatumType};
+pub use crate::profiles::{ArrayData, ArrayOptions, ArrayProfile, ByteOrder, DataProfile, DatumArray, DatumMap, DeriveProfile, EnumTagging, EnumVariants, MappedData, MappedOptions, MappedProfile, ProfileFromData, ProfileToData, VERSION_KEY, convert_data_profile};
+#[cfg(feature = "derive")]
+pub use mangle_serde_derive::Serde;
+#[cfg(feature = "toml")]
+pub use crate::toml::TOMLSerde;
+#[cfg(feature = "bin")]
+pub use crate::binary::{BinCursor, BinReader, BinSerde, BinWriter};
+#[cfg(feature = "json")]
+pub use crate::json::JSONSerde;
 
 #[cfg(feature = "toml")]
 mod toml;
@@ -53,11 +65,12 @@ mod json;
 #[derive(Debug)]
 pub enum DeserializationError {
 	/// An expected field could not be found
-	/// Contains the field name
+	/// Contains the path to the field
 	MissingField(String),
 	/// An expected field has an unexpected data type
 	InvalidType {
-		/// The name of the field.
+		/// The path to the field, such as `servers[2].tls.cert_path`.
+		/// Items of profiles based on ArrayData are identified by their index.
 		/// The name "\<global\>" implies that the entire serialized data is in the wrong format
 		field: String,
 		/// The expected type of the field
@@ -67,7 +80,7 @@ pub enum DeserializationError {
 	},
 	/// An expected field does not contain any of the expected data
 	NoMatch {
-		/// The name of the field
+		/// The path to the field
 		field: String,
 		/// The actual data contained in the field
 		actual: String,
@@ -76,6 +89,24 @@ pub enum DeserializationError {
 	FromUTF8Error(FromUtf8Error),
 	/// The data we are deserializing from is too short
 	UnexpectedEOF,
+	/// An error occurred while reading the data
+	IOError(std::io::Error),
+	/// The data was written by a newer version than the type being deserialized
+	UnsupportedVersion {
+		/// The path to the value
+		field: String,
+		/// The version of the data
+		version: u8,
+		/// The latest version that the type can read
+		latest: u8,
+	},
+	/// A map has entries that were not deserialized, while unknown entries are denied
+	UnknownField {
+		/// The path to the map
+		field: String,
+		/// The keys of the entries that were not deserialized, in sorted order
+		keys: Vec<String>,
+	},
 	#[cfg(feature = "toml")]
 	/// An error occurred while parsing TOML formatted data
 	TOMLError(TOMLError),
@@ -86,18 +117,208 @@ pub enum DeserializationError {
 
 
 impl DeserializationError {
-	/// Sets the field parameter if possible.
+	/// Returns the field parameter, if there is one
+	fn field(&self) -> Option<&str> {
+		match self {
+			Self::MissingField(field) => Some(field),
+			Self::InvalidType { field, .. } => Some(field),
+			Self::NoMatch { field, .. } => Some(field),
+			Self::UnsupportedVersion { field, .. } => Some(field),
+			Self::UnknownField { field, .. } => Some(field),
+			_ => None
+		}
+	}
+	/// Prepends the given segment to the path in the field parameter, if there is one.
 	/// This is to help with debugging
-	fn set_field(&mut self, new_field: String) {
-		*match self {
-			Self::MissingField(_) => return,
+	fn prepend_field(&mut self, segment: &str) {
+		let field = match self {
+			Self::MissingField(field) => field,
 			Self::InvalidType { field, .. } => field,
 			Self::NoMatch { field, .. } => field,
+			Self::UnsupportedVersion { field, .. } => field,
+			Self::UnknownField { field, .. } => field,
+			#[cfg(feature = "toml")]
 			Self::TOMLError(_) => return,
+			#[cfg(feature = "json")]
 			Self::JSONError(_) => return,
 			DeserializationError::FromUTF8Error(_) => return,
-			DeserializationError::UnexpectedEOF => return
-		} = new_field;
+			DeserializationError::UnexpectedEOF => return,
+			DeserializationError::IOError(_) => return
+		};
+		*field = join_path(segment, field);
+	}
+}
+
+
+impl Display for DeserializationError {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::MissingField(field) => write!(f, "missing field `{}`", field),
+			Self::InvalidType { field, expected, actual } => write!(f, "field `{}`: expected {}, found {}", field, expected, actual),
+			Self::NoMatch { field, actual } => write!(f, "field `{}`: {} does not match any expected value", field, actual),
+			Self::FromUTF8Error(e) => write!(f, "invalid utf-8 string: {}", e),
+			Self::UnexpectedEOF => write!(f, "unexpected end of data"),
+			Self::IOError(e) => write!(f, "could not read data: {}", e),
+			Self::UnsupportedVersion { field, version, latest } => write!(f, "field `{}`: version {} is newer than the latest version {}", field, version, latest),
+			Self::UnknownField { field, keys } if field.is_empty() => write!(f, "unknown keys `{}`", keys.join("`, `")),
+			Self::UnknownField { field, keys } => write!(f, "field `{}`: unknown keys `{}`", field, keys.join("`, `")),
+			#[cfg(feature = "toml")]
+			Self::TOMLError(e) => write!(f, "invalid TOML: {}", e),
+			#[cfg(feature = "json")]
+			Self::JSONError(e) => write!(f, "invalid JSON: {}", e),
+		}
+	}
+}
+
+
+impl From<std::io::Error> for DeserializationError {
+	fn from(e: std::io::Error) -> Self {
+		match e.kind() {
+			std::io::ErrorKind::UnexpectedEof => Self::UnexpectedEOF,
+			_ => Self::IOError(e)
+		}
+	}
+}
+
+
+// Allows conversions that cannot fail to be used wherever a conversion that can fail is expected
+impl From<Infallible> for DeserializationError {
+	fn from(e: Infallible) -> Self {
+		match e {}
+	}
+}
+
+
+impl Error for DeserializationError {
+	fn source(&self) -> Option<&(dyn Error + 'static)> {
+		match self {
+			Self::FromUTF8Error(e) => Some(e),
+			Self::IOError(e) => Some(e),
+			#[cfg(feature = "toml")]
+			Self::TOMLError(e) => Some(e),
+			#[cfg(feature = "json")]
+			Self::JSONError(e) => Some(e),
+			_ => None
+		}
+	}
+}
+
+
+/// Joins a segment of a field path onto the rest of the path that follows it.
+/// Names are separated by periods, while indices are written in brackets, such as `servers[2].tls.cert_path`
+pub(crate) fn join_path(segment: &str, path: &str) -> String {
+	if path.is_empty() {
+		segment.into()
+	} else if segment.is_empty() || path.starts_with('[') {
+		format!("{}{}", segment, path)
+	} else {
+		format!("{}.{}", segment, path)
+	}
+}
+
+
+/// An error that can occur when trying to serialize data
+#[derive(Debug)]
+pub enum SerializationError {
+	/// A Serde implementation returned an error while serializing
+	Serde {
+		/// The path to the field that could not be serialized.
+		/// The name "\<global\>" implies that the error is not tied to a field
+		field: String,
+		/// The error that was returned
+		error: DeserializationError,
+	},
+	/// The length of an item does not fit in the size chosen for its length prefix
+	LengthOverflow {
+		/// The path to the item.
+		/// The name "\<global\>" implies that the error is not tied to a field
+		field: String,
+		/// The length of the item
+		length: usize,
+		/// The size chosen for the length
+		size: DatumSize,
+	},
+	#[cfg(feature = "toml")]
+	/// Some data could not be written as TOML
+	TOMLError {
+		/// The path to the value that could not be written.
+		/// The name "\<global\>" implies that the error is not tied to a field
+		field: String,
+		/// The error that was returned
+		error: TOMLSerError,
+	},
+	/// An error occurred while writing the data
+	IOError(std::io::Error),
+}
+
+
+impl From<DeserializationError> for SerializationError {
+	fn from(error: DeserializationError) -> Self {
+		let field = match error.field() {
+			Some(field) if !field.is_empty() => field.into(),
+			_ => "<global>".into()
+		};
+		Self::Serde { field, error }
+	}
+}
+
+
+impl From<std::io::Error> for SerializationError {
+	fn from(e: std::io::Error) -> Self {
+		Self::IOError(e)
+	}
+}
+
+
+impl Display for SerializationError {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::Serde { field, error } => write!(f, "could not serialize field `{}`: {}", field, error),
+			Self::LengthOverflow { field, length, size } => write!(f, "could not write field `{}`: length {} is over the maximum of {}", field, length, size.max_len()),
+			#[cfg(feature = "toml")]
+			Self::TOMLError { field, error } => write!(f, "could not write field `{}` as TOML: {}", field, error),
+			Self::IOError(e) => write!(f, "could not write data: {}", e),
+		}
+	}
+}
+
+
+impl Error for SerializationError {
+	fn source(&self) -> Option<&(dyn Error + 'static)> {
+		match self {
+			Self::Serde { error, .. } => Some(error),
+			Self::LengthOverflow { .. } => None,
+			#[cfg(feature = "toml")]
+			Self::TOMLError { error, .. } => Some(error),
+			Self::IOError(e) => Some(e),
+		}
+	}
+}
+
+
+impl SerializationError {
+	/// Prepends the given segment to the path of errors raised while writing data.
+	/// Errors from Serde implementations already hold their full path
+	#[cfg(any(feature = "toml", feature = "bin"))]
+	pub(crate) fn prepend_field(&mut self, segment: &str) {
+		let field = match self {
+			Self::LengthOverflow { field, .. } => field,
+			#[cfg(feature = "toml")]
+			Self::TOMLError { field, .. } => field,
+			Self::Serde { .. } | Self::IOError(_) => return
+		};
+		*field = join_path(segment, field);
+	}
+	/// Gives errors raised while writing data the path "\<global\>" if they are not tied to a field
+	#[cfg(any(feature = "toml", feature = "bin"))]
+	pub(crate) fn globalize(mut self) -> Self {
+		match &mut self {
+			Self::LengthOverflow { field, .. } if field.is_empty() => *field = "<global>".into(),
+			#[cfg(feature = "toml")]
+			Self::TOMLError { field, .. } if field.is_empty() => *field = "<global>".into(),
+			_ => {}
+		}
+		self
 	}
 }
 
@@ -105,18 +326,18 @@ impl DeserializationError {
 /// Used to convert a result into a deserialization result
 trait TransformResult<T> {
 	/// Converts the result into a deserialization result.
-	/// Sets some fields in deserialization error if possible
-	fn transform(self, new_field: String) -> Result<T, DeserializationError>;
+	/// Prepends the given segment to the field path in the deserialization error if possible
+	fn transform(self, segment: String) -> Result<T, DeserializationError>;
 }
 
 
 impl<T, E: Into<DeserializationError>> TransformResult<T> for Result<T, E> {
-	fn transform(self, new_field: String) -> Result<T, DeserializationError> {
+	fn transform(self, segment: String) -> Result<T, DeserializationError> {
 		match self {
 			Ok(x) => Ok(x),
 			Err(e) => {
 				let mut e = e.into();
-				e.set_field(new_field);
+				e.prepend_field(&segment);
 				Err(e)
 			}
 		}
@@ -134,65 +355,202 @@ impl From<FromUtf8Error> for DeserializationError {
 /// Serialization and Deserialization Trait.
 /// Implement this on types that you wish to serialize or deserialize.
 /// Takes in a DataProfile Alias as a type parameter
-pub trait Serde<T: DataProfile>: Default {
+///
+/// Deserializing into a new value starts from its default value, so it requires Default.
+/// Types that cannot implement Default can still be deserialized into an existing value with deserialize_into,
+/// or be constructed from a data profile by implementing DeserializeNew
+pub trait Serde<T: DataProfile>: Sized {
+	/// The version of the data that serde currently writes.
+	/// Increase this whenever the fields that serde writes change,
+	/// so that data written by older versions can still be read with the help of migrate.
+	/// SerializeRef and DeserializeNew use this version as well
+	///
+	/// Profiles based on ArrayData only record the version if their versioned option is set.
+	/// EfficientProfile does not set it, so its data is always read as the current version,
+	/// and binary data that must stay readable across versions needs a profile with `versioned: true`
+	const VERSION: u8 = 0;
+
 	/// This function is called whenever serialization or deserialization is required.
 	/// DataProfile offers multiple methods for symmetric coding to save you time here.
 	/// However, DataProfile also allows you to check if the method is being called for serialization or deserialization
+	///
+	/// While deserializing, data.version() is the version that the data was written with,
+	/// so that only the fields that existed in that version are read
 	fn serde(&mut self, data: &mut T) -> Result<(), DeserializationError>;
 
+	/// Upgrades a value that was read from data of the given older version to the next version.
+	/// After serde has read data of an older version, this is called once for every version up to VERSION, oldest first.
+	/// This is the place to fill in fields that the older version did not have
+	fn migrate(&mut self, _from_version: u8) -> Result<(), DeserializationError> {
+		Ok(())
+	}
+
 	/// Serializes self into a data profile.
 	/// You will not need to call this directly.
 	/// Consider using serialize
+	///
+	/// If serde returns an error, the data profile holds whatever was serialized before the error.
+	/// Use try_into_data_profile to find out about the error instead
 	fn into_data_profile(mut self) -> T {
 		let mut data = T::serial_ready();
-		let _ = self.serde(&mut data);
+		let _ = serde_value(&mut self, &mut data);
 		data
 	}
 
+	/// Serializes self into a data profile, returning any error that serde returns.
+	/// You will not need to call this directly.
+	/// Consider using try_serialize
+	fn try_into_data_profile(mut self) -> Result<T, SerializationError> {
+		let mut data = T::serial_ready();
+		serde_value(&mut self, &mut data)?;
+		Ok(data)
+	}
+
 	/// Deserializes the given data profile into Self
 	/// You will not need to call this directly.
 	/// Consider using deserialize
-	fn from_data_profile(mut data: T) -> Result<Self, DeserializationError> {
+	fn from_data_profile(data: T) -> Result<Self, DeserializationError> where Self: Default {
 		let mut deser = Self::default();
-		deser.serde(&mut data)?;
+		deser.update_from_data_profile(data)?;
 		Ok(deser)
 	}
 
+	/// Deserializes the given data profile into self, updating the existing value.
+	/// You will not need to call this directly.
+	/// Consider using deserialize_into
+	fn update_from_data_profile(&mut self, mut data: T) -> Result<(), DeserializationError> {
+		serde_value(self, &mut data)
+	}
+
 	/// Serialize to any type that can be constructed from a DataProfile.
 	/// For now, that is only a toml::Value, json::Value, and Vec<u8>
 	fn serialize<S>(self) -> S where T: ProfileToData<S> {
 		self.into_data_profile().into()
 	}
+
+	/// Serialize to any type that can be constructed from a DataProfile.
+	/// Returns an error if serde returns an error
+	fn try_serialize<S>(self) -> Result<S, SerializationError> where T: ProfileToData<S> {
+		self.try_into_data_profile()?.try_into_data()
+	}
 	
 	/// Deserialize from an type that can transform into a DataProfile.
 	/// For now, that is only a toml::Value, json::Value, and Vec\<u8\>
-	fn deserialize<D>(data: D) -> Result<Self, DeserializationError> where T: ProfileFromData<D> {
+	fn deserialize<D>(data: D) -> Result<Self, DeserializationError> where T: ProfileFromData<D>, Self: Default {
 		Self::from_data_profile(T::try_from(data)?)
 	}
+
+	/// Deserialize from an type that can transform into a DataProfile, updating self instead of creating a new value.
+	/// If an error is returned, self may have been partially updated
+	fn deserialize_into<D>(&mut self, data: D) -> Result<(), DeserializationError> where T: ProfileFromData<D> {
+		self.update_from_data_profile(T::try_from(data)?)
+	}
+}
+
+
+/// Deserialization by construction, for types that cannot implement Default.
+/// Rather than filling in an existing value, the value is built from what is read out of the data profile,
+/// which allows any invariants of the type to be checked before it exists.
+///
+/// Data is read with the VERSION of Serde, and values read from older versions are migrated with the migrate of Serde
+pub trait DeserializeNew<T: DataProfile>: Serde<T> {
+	/// Reads the fields out of the given data profile, which is always ready for deserialization, and builds Self
+	fn deserialize_fields(data: &mut T) -> Result<Self, DeserializationError>;
+
+	/// Constructs Self from the given data profile.
+	/// You will not need to call this directly.
+	/// Consider using deserialize_new
+	fn new_from_data_profile(mut data: T) -> Result<Self, DeserializationError> {
+		deserialize_new_value(&mut data)
+	}
+
+	/// Constructs Self from an type that can transform into a DataProfile
+	fn deserialize_new<D>(data: D) -> Result<Self, DeserializationError> where T: ProfileFromData<D> {
+		Self::new_from_data_profile(T::try_from(data)?)
+	}
+}
+
+
+/// Serializes or deserializes a value with the given data profile, recording or reading the version of the value.
+/// Once the value is deserialized, the data profile checks what is left of the data,
+/// and values read from older versions are migrated up to the current version
+pub(crate) fn serde_value<P: DataProfile, T: Serde<P>>(value: &mut T, data: &mut P) -> Result<(), DeserializationError> {
+	data.apply_version(T::VERSION)?;
+	value.serde(data)?;
+	if data.is_serial() {
+		return Ok(())
+	}
+	data.finish()?;
+	for version in data.version()..T::VERSION {
+		value.migrate(version)?;
+	}
+	Ok(())
+}
+
+
+/// Constructs a value from the given data profile, reading the version of the value first.
+/// Once the value is constructed, the data profile checks what is left of the data,
+/// and values read from older versions are migrated up to the current version
+pub(crate) fn deserialize_new_value<P: DataProfile, T: DeserializeNew<P>>(data: &mut P) -> Result<T, DeserializationError> {
+	data.apply_version(<T as Serde<P>>::VERSION)?;
+	let mut value = T::deserialize_fields(data)?;
+	data.finish()?;
+	for version in data.version()..<T as Serde<P>>::VERSION {
+		value.migrate(version)?;
+	}
+	Ok(value)
 }
 
 
+/// Serialization by reference, for values that must be left intact after serializing.
+/// Serde takes the fields of the value it serializes, to avoid cloning them,
+/// whereas this trait only reads the value and clones each field into the data profile.
+///
+/// Unlike Serde, this only describes serialization, so it should write the same fields that serde would,
+/// and the data is recorded with the VERSION of Serde.
+/// The derive macro implements this alongside Serde
+pub trait SerializeRef<T: DataProfile>: Serde<T> {
+	/// Writes the fields of self into the given data profile, which is always ready for serialization
+	fn serialize_fields(&self, data: &mut T);
+
+	/// Serializes self into a data profile, leaving self intact.
+	/// You will not need to call this directly.
+	/// Consider using serialize_ref
+	fn to_data_profile(&self) -> T {
+		let mut data = T::serial_ready();
+		// Recording a version cannot fail while serializing
+		let _ = data.apply_version(<Self as Serde<T>>::VERSION);
+		self.serialize_fields(&mut data);
+		data
+	}
+
+	/// Serialize to any type that can be constructed from a DataProfile, leaving self intact.
+	/// For now, that is only a toml::Value, json::Value, and Vec<u8>
+	fn serialize_ref<S>(&self) -> S where T: ProfileToData<S> {
+		self.to_data_profile().into()
+	}
+}
+
 make_data_profile!(
 	/// A data representation profile that should be used for dealing with serialized data that is human readable
 	ReadableProfile use MappedData
 );
 
 make_data_profile!(
-	/// A data representation profile that should be used for dealing with serialized data that is space efficient
-	EfficientProfile use ArrayData
+	/// A data representation profile that should be used for dealing with serialized data that is space efficient.
+	/// No versions are written, so data written before a change to the fields of a type cannot be read after it
+	EfficientProfile use ArrayData { pack_bools: true }
 );
 
 
 #[cfg(test)]
 mod tests {
-	use crate::toml::TOMLSerde;
-	use crate::binary::BinSerde;
-	use crate::json::JSONSerde;
-	use crate::profiles::{convert_data_profile};
+	use std::collections::{BTreeMap, HashMap, VecDeque};
+
 	use super::*;
 
 	make_data_profile!(
-		///
+		/// A profile for testing conversions between profiles
 		TestProfile use MappedData
 	);
 
@@ -226,51 +584,2023 @@ mod tests {
 		}
 	}
 	
+	#[cfg(feature = "toml")]
 	impl TOMLSerde<ReadableProfile> for TestStruct {}
+	#[cfg(feature = "bin")]
 	impl BinSerde<EfficientProfile> for TestStruct {}
+	#[cfg(feature = "json")]
 	impl JSONSerde<ReadableProfile> for TestStruct { const TAB_SIZE: u16 = 4; }
 	
-	#[test]
-	fn test_serde() {
-		let src = TestStruct {
-			name: "ferus",
-			age: 52,
-			id: "gangnam".into(),
-		};
-		let ser = src.serialize_toml();
-		println!("{}", ser);
-		let deser = TestStruct::deserialize_toml(ser).unwrap();
-		println!("{:?}", deser);
+	#[derive(Default, Debug, PartialEq)]
+	struct SignedStruct {
+		temperature: i8,
+		offset: i16,
+		delta: i32,
+		timestamp: i64,
 	}
 
-	#[test]
-	fn test_serde2() {
-		let src = TestStruct {
-			name: "ferus",
-			age: 52,
-			id: "gangnam".into(),
-		};
-		let ser = src.serialize_bin();
-		println!("{:?}", ser);
-		let deser = TestStruct::deserialize_bin(ser).unwrap();
-		println!("{:?}", deser);
+	impl Serde<ReadableProfile> for SignedStruct {
+		fn serde(&mut self, data: &mut ReadableProfile) -> Result<(), DeserializationError> {
+			data.serde_entry("temperature", &mut self.temperature)?;
+			data.serde_entry("offset", &mut self.offset)?;
+			data.serde_entry("delta", &mut self.delta)?;
+			data.serde_entry("timestamp", &mut self.timestamp)
+		}
 	}
 
-	#[test]
-	fn test_serde3() {
-		let src = TestStruct {
-			name: "ferus",
-			age: 52,
-			id: "gangnam".into(),
-		};
-		let test: TestProfile = convert_data_profile(Serde::<ReadableProfile>::into_data_profile(src));
-		let ser: extern_json::JsonValue = ProfileToData::into(test);
-		println!("{}", ser);
-		let deser: TestStruct = Serde::<ReadableProfile>::from_data_profile(ProfileFromData::try_from(ser).unwrap()).unwrap();
-		println!("{:?}", deser);
+	impl Serde<EfficientProfile> for SignedStruct {
+		fn serde(&mut self, data: &mut EfficientProfile) -> Result<(), DeserializationError> {
+			data.serde_item(&mut self.temperature)?;
+			data.serde_item(&mut self.offset)?;
+			data.serde_item(&mut self.delta)?;
+			data.serde_item(&mut self.timestamp)
+		}
 	}
 
-	#[test]
+	#[cfg(feature = "toml")]
+	impl TOMLSerde<ReadableProfile> for SignedStruct {}
+	#[cfg(feature = "bin")]
+	impl BinSerde<EfficientProfile> for SignedStruct {}
+	#[cfg(feature = "json")]
+	impl JSONSerde<ReadableProfile> for SignedStruct { const TAB_SIZE: u16 = 4; }
+
+	fn make_signed() -> SignedStruct {
+		SignedStruct {
+			temperature: -40,
+			offset: 1200,
+			delta: -70000,
+			timestamp: -1_650_000_000_000,
+		}
+	}
+
+	#[derive(Default, Debug)]
+	struct FloatStruct {
+		ratio: f32,
+		latitude: f64,
+		missing: f64,
+		limit: f32,
+	}
+
+	impl Serde<ReadableProfile> for FloatStruct {
+		fn serde(&mut self, data: &mut ReadableProfile) -> Result<(), DeserializationError> {
+			data.serde_entry("ratio", &mut self.ratio)?;
+			data.serde_entry("latitude", &mut self.latitude)?;
+			data.serde_entry("missing", &mut self.missing)?;
+			data.serde_entry("limit", &mut self.limit)
+		}
+	}
+
+	impl Serde<EfficientProfile> for FloatStruct {
+		fn serde(&mut self, data: &mut EfficientProfile) -> Result<(), DeserializationError> {
+			data.serde_item(&mut self.ratio)?;
+			data.serde_item(&mut self.latitude)?;
+			data.serde_item(&mut self.missing)?;
+			data.serde_item(&mut self.limit)
+		}
+	}
+
+	#[cfg(feature = "toml")]
+	impl TOMLSerde<ReadableProfile> for FloatStruct {}
+	#[cfg(feature = "bin")]
+	impl BinSerde<EfficientProfile> for FloatStruct {}
+	#[cfg(feature = "json")]
+	impl JSONSerde<ReadableProfile> for FloatStruct { const TAB_SIZE: u16 = 4; }
+
+	fn make_float() -> FloatStruct {
+		FloatStruct {
+			ratio: 0.1,
+			latitude: -33.868_819_7,
+			missing: f64::NAN,
+			limit: f32::NEG_INFINITY,
+		}
+	}
+
+	fn assert_float_eq(deser: FloatStruct) {
+		let src = make_float();
+		assert_eq!(deser.ratio.to_bits(), src.ratio.to_bits());
+		assert_eq!(deser.latitude.to_bits(), src.latitude.to_bits());
+		assert!(deser.missing.is_nan());
+		assert_eq!(deser.limit.to_bits(), src.limit.to_bits());
+	}
+
+	make_data_profile!(
+		/// A profile for testing booleans that are not packed
+		LooseProfile use ArrayData
+	);
+
+	#[derive(Default, Debug, PartialEq)]
+	struct FlagStruct {
+		enabled: bool,
+		verbose: bool,
+		debug: bool,
+		level: u8,
+		cached: bool,
+	}
+
+	impl Serde<ReadableProfile> for FlagStruct {
+		fn serde(&mut self, data: &mut ReadableProfile) -> Result<(), DeserializationError> {
+			data.serde_entry("enabled", &mut self.enabled)?;
+			data.serde_entry("verbose", &mut self.verbose)?;
+			data.serde_entry("debug", &mut self.debug)?;
+			data.serde_entry("level", &mut self.level)?;
+			data.serde_entry("cached", &mut self.cached)
+		}
+	}
+
+	impl Serde<EfficientProfile> for FlagStruct {
+		fn serde(&mut self, data: &mut EfficientProfile) -> Result<(), DeserializationError> {
+			data.serde_item(&mut self.enabled)?;
+			data.serde_item(&mut self.verbose)?;
+			data.serde_item(&mut self.debug)?;
+			data.serde_item(&mut self.level)?;
+			data.serde_item(&mut self.cached)
+		}
+	}
+
+	impl Serde<LooseProfile> for FlagStruct {
+		fn serde(&mut self, data: &mut LooseProfile) -> Result<(), DeserializationError> {
+			data.serde_item(&mut self.enabled)?;
+			data.serde_item(&mut self.verbose)?;
+			data.serde_item(&mut self.debug)?;
+			data.serde_item(&mut self.level)?;
+			data.serde_item(&mut self.cached)
+		}
+	}
+
+	#[cfg(feature = "toml")]
+	impl TOMLSerde<ReadableProfile> for FlagStruct {}
+	#[cfg(feature = "bin")]
+	impl BinSerde<EfficientProfile> for FlagStruct {}
+	#[cfg(feature = "json")]
+	impl JSONSerde<ReadableProfile> for FlagStruct { const TAB_SIZE: u16 = 4; }
+
+	fn make_flags() -> FlagStruct {
+		FlagStruct {
+			enabled: true,
+			verbose: false,
+			debug: true,
+			level: 3,
+			cached: true,
+		}
+	}
+
+	#[derive(Default, Debug, PartialEq)]
+	struct OptionalStruct {
+		nickname: Option<String>,
+		age: Option<u64>,
+	}
+
+	impl Serde<ReadableProfile> for OptionalStruct {
+		fn serde(&mut self, data: &mut ReadableProfile) -> Result<(), DeserializationError> {
+			data.serde_optional_entry("nickname", &mut self.nickname)?;
+			data.serde_optional_entry("age", &mut self.age)
+		}
+	}
+
+	impl Serde<EfficientProfile> for OptionalStruct {
+		fn serde(&mut self, data: &mut EfficientProfile) -> Result<(), DeserializationError> {
+			data.serde_optional_item(&mut self.nickname)?;
+			data.serde_optional_item(&mut self.age)
+		}
+	}
+
+	#[cfg(feature = "toml")]
+	impl TOMLSerde<ReadableProfile> for OptionalStruct {}
+	#[cfg(feature = "bin")]
+	impl BinSerde<EfficientProfile> for OptionalStruct {}
+	#[cfg(feature = "json")]
+	impl JSONSerde<ReadableProfile> for OptionalStruct { const TAB_SIZE: u16 = 4; }
+
+	fn make_optional() -> OptionalStruct {
+		OptionalStruct {
+			nickname: None,
+			age: Some(52),
+		}
+	}
+
+	#[derive(Default, Debug, PartialEq)]
+	struct PetStruct {
+		species: String,
+		legs: u8,
+		vaccinated: bool,
+	}
+
+	impl Serde<ReadableProfile> for PetStruct {
+		fn serde(&mut self, data: &mut ReadableProfile) -> Result<(), DeserializationError> {
+			data.serde_entry("species", &mut self.species)?;
+			data.serde_entry("legs", &mut self.legs)?;
+			data.serde_entry("vaccinated", &mut self.vaccinated)
+		}
+	}
+
+	impl Serde<EfficientProfile> for PetStruct {
+		fn serde(&mut self, data: &mut EfficientProfile) -> Result<(), DeserializationError> {
+			data.serde_item(&mut self.species)?;
+			data.serde_item(&mut self.legs)?;
+			data.serde_item(&mut self.vaccinated)
+		}
+	}
+
+	impl SerializeRef<ReadableProfile> for PetStruct {
+		fn serialize_fields(&self, data: &mut ReadableProfile) {
+			data.serialize_entry("species", self.species.clone());
+			data.serialize_entry("legs", self.legs);
+			data.serialize_entry("vaccinated", self.vaccinated);
+		}
+	}
+
+	impl SerializeRef<EfficientProfile> for PetStruct {
+		fn serialize_fields(&self, data: &mut EfficientProfile) {
+			data.serialize_item(self.species.clone());
+			data.serialize_item(self.legs);
+			data.serialize_item(self.vaccinated);
+		}
+	}
+
+	#[derive(Default, Debug, PartialEq)]
+	struct OwnerStruct {
+		name: String,
+		pet: PetStruct,
+		verified: bool,
+	}
+
+	impl Serde<ReadableProfile> for OwnerStruct {
+		fn serde(&mut self, data: &mut ReadableProfile) -> Result<(), DeserializationError> {
+			data.serde_entry("name", &mut self.name)?;
+			data.serde_nested_entry("pet", &mut self.pet)?;
+			data.serde_entry("verified", &mut self.verified)
+		}
+	}
+
+	impl Serde<EfficientProfile> for OwnerStruct {
+		fn serde(&mut self, data: &mut EfficientProfile) -> Result<(), DeserializationError> {
+			data.serde_item(&mut self.name)?;
+			data.serde_nested_item(&mut self.pet)?;
+			data.serde_item(&mut self.verified)
+		}
+	}
+
+	impl SerializeRef<ReadableProfile> for OwnerStruct {
+		fn serialize_fields(&self, data: &mut ReadableProfile) {
+			data.serialize_entry("name", self.name.clone());
+			data.serialize_nested_entry_ref("pet", &self.pet);
+			data.serialize_entry("verified", self.verified);
+		}
+	}
+
+	impl SerializeRef<EfficientProfile> for OwnerStruct {
+		fn serialize_fields(&self, data: &mut EfficientProfile) {
+			data.serialize_item(self.name.clone());
+			data.serialize_nested_item_ref(&self.pet);
+			data.serialize_item(self.verified);
+		}
+	}
+
+	#[cfg(feature = "toml")]
+	impl TOMLSerde<ReadableProfile> for OwnerStruct {}
+	#[cfg(feature = "bin")]
+	impl BinSerde<EfficientProfile> for OwnerStruct {}
+	#[cfg(feature = "json")]
+	impl JSONSerde<ReadableProfile> for OwnerStruct { const TAB_SIZE: u16 = 4; }
+
+	fn make_owner() -> OwnerStruct {
+		OwnerStruct {
+			name: "ferus".into(),
+			pet: PetStruct {
+				species: "cat".into(),
+				legs: 4,
+				vaccinated: true,
+			},
+			verified: false,
+		}
+	}
+
+	#[derive(Default, Debug, PartialEq)]
+	struct ShelterStruct {
+		tags: Vec<String>,
+		ratings: Vec<i32>,
+		pets: Vec<PetStruct>,
+	}
+
+	impl Serde<ReadableProfile> for ShelterStruct {
+		fn serde(&mut self, data: &mut ReadableProfile) -> Result<(), DeserializationError> {
+			data.serde_seq_entry("tags", &mut self.tags)?;
+			data.serde_seq_entry("ratings", &mut self.ratings)?;
+			data.serde_nested_seq_entry("pets", &mut self.pets)
+		}
+	}
+
+	impl Serde<EfficientProfile> for ShelterStruct {
+		fn serde(&mut self, data: &mut EfficientProfile) -> Result<(), DeserializationError> {
+			data.serde_seq_item(&mut self.tags)?;
+			data.serde_seq_item(&mut self.ratings)?;
+			data.serde_nested_seq_item(&mut self.pets)
+		}
+	}
+
+	impl SerializeRef<ReadableProfile> for ShelterStruct {
+		fn serialize_fields(&self, data: &mut ReadableProfile) {
+			data.serialize_seq_entry("tags", self.tags.clone());
+			data.serialize_seq_entry("ratings", self.ratings.clone());
+			data.serialize_nested_seq_entry_ref("pets", &self.pets);
+		}
+	}
+
+	impl SerializeRef<EfficientProfile> for ShelterStruct {
+		fn serialize_fields(&self, data: &mut EfficientProfile) {
+			data.serialize_seq_item(self.tags.clone());
+			data.serialize_seq_item(self.ratings.clone());
+			data.serialize_nested_seq_item_ref(&self.pets);
+		}
+	}
+
+	#[cfg(feature = "toml")]
+	impl TOMLSerde<ReadableProfile> for ShelterStruct {}
+	#[cfg(feature = "bin")]
+	impl BinSerde<EfficientProfile> for ShelterStruct {}
+	#[cfg(feature = "json")]
+	impl JSONSerde<ReadableProfile> for ShelterStruct { const TAB_SIZE: u16 = 4; }
+
+	fn make_shelter() -> ShelterStruct {
+		ShelterStruct {
+			tags: vec!["cozy".into(), "open".into()],
+			ratings: vec![5, -1, 3],
+			pets: vec![
+				make_owner().pet,
+				PetStruct {
+					species: "snake".into(),
+					legs: 0,
+					vaccinated: false,
+				},
+			],
+		}
+	}
+
+	#[derive(Default, Debug, PartialEq)]
+	struct DirectoryStruct {
+		visits: HashMap<String, u64>,
+		ports: BTreeMap<u16, String>,
+		pets: BTreeMap<String, PetStruct>,
+	}
+
+	impl Serde<ReadableProfile> for DirectoryStruct {
+		fn serde(&mut self, data: &mut ReadableProfile) -> Result<(), DeserializationError> {
+			data.serde_map_entry("visits", &mut self.visits)?;
+			data.serde_map_entry("ports", &mut self.ports)?;
+			data.serde_nested_map_entry("pets", &mut self.pets)
+		}
+	}
+
+	impl Serde<EfficientProfile> for DirectoryStruct {
+		fn serde(&mut self, data: &mut EfficientProfile) -> Result<(), DeserializationError> {
+			data.serde_map_item(&mut self.visits)?;
+			data.serde_map_item(&mut self.ports)?;
+			data.serde_nested_map_item(&mut self.pets)
+		}
+	}
+
+	#[cfg(feature = "toml")]
+	impl TOMLSerde<ReadableProfile> for DirectoryStruct {}
+	#[cfg(feature = "bin")]
+	impl BinSerde<EfficientProfile> for DirectoryStruct {}
+	#[cfg(feature = "json")]
+	impl JSONSerde<ReadableProfile> for DirectoryStruct { const TAB_SIZE: u16 = 4; }
+
+	fn make_directory() -> DirectoryStruct {
+		DirectoryStruct {
+			visits: HashMap::from([("home".into(), 12), ("about".into(), 3)]),
+			ports: BTreeMap::from([(80, "http".into()), (443, "https".into())]),
+			pets: BTreeMap::from([("tom".into(), make_owner().pet)]),
+		}
+	}
+
+	#[derive(Default, Debug, PartialEq)]
+	enum ShapeEnum {
+		Circle { radius: f64 },
+		Rect { width: u32, height: u32 },
+		#[default]
+		Empty,
+	}
+
+	impl EnumVariants for ShapeEnum {
+		const VARIANTS: &'static [&'static str] = &["circle", "rect", "empty"];
+
+		fn variant_index(&self) -> usize {
+			match self {
+				ShapeEnum::Circle { .. } => 0,
+				ShapeEnum::Rect { .. } => 1,
+				ShapeEnum::Empty => 2
+			}
+		}
+
+		fn from_variant_index(index: usize) -> Option<Self> {
+			Some(match index {
+				0 => ShapeEnum::Circle { radius: 0.0 },
+				1 => ShapeEnum::Rect { width: 0, height: 0 },
+				2 => ShapeEnum::Empty,
+				_ => return None
+			})
+		}
+	}
+
+	impl Serde<ReadableProfile> for ShapeEnum {
+		fn serde(&mut self, data: &mut ReadableProfile) -> Result<(), DeserializationError> {
+			match self {
+				ShapeEnum::Circle { radius } => data.serde_entry("radius", radius),
+				ShapeEnum::Rect { width, height } => {
+					data.serde_entry("width", width)?;
+					data.serde_entry("height", height)
+				}
+				ShapeEnum::Empty => Ok(())
+			}
+		}
+	}
+
+	impl Serde<EfficientProfile> for ShapeEnum {
+		fn serde(&mut self, data: &mut EfficientProfile) -> Result<(), DeserializationError> {
+			match self {
+				ShapeEnum::Circle { radius } => data.serde_item(radius),
+				ShapeEnum::Rect { width, height } => {
+					data.serde_item(width)?;
+					data.serde_item(height)
+				}
+				ShapeEnum::Empty => Ok(())
+			}
+		}
+	}
+
+	impl SerializeRef<ReadableProfile> for ShapeEnum {
+		fn serialize_fields(&self, data: &mut ReadableProfile) {
+			match self {
+				ShapeEnum::Circle { radius } => data.serialize_entry("radius", *radius),
+				ShapeEnum::Rect { width, height } => {
+					data.serialize_entry("width", *width);
+					data.serialize_entry("height", *height);
+				}
+				ShapeEnum::Empty => {}
+			}
+		}
+	}
+
+	impl SerializeRef<EfficientProfile> for ShapeEnum {
+		fn serialize_fields(&self, data: &mut EfficientProfile) {
+			match self {
+				ShapeEnum::Circle { radius } => data.serialize_item(*radius),
+				ShapeEnum::Rect { width, height } => {
+					data.serialize_item(*width);
+					data.serialize_item(*height);
+				}
+				ShapeEnum::Empty => {}
+			}
+		}
+	}
+
+	#[derive(Default, Debug, PartialEq)]
+	struct CanvasStruct {
+		external: ShapeEnum,
+		unit: ShapeEnum,
+		internal: ShapeEnum,
+		adjacent: ShapeEnum,
+		untagged: ShapeEnum,
+	}
+
+	impl Serde<ReadableProfile> for CanvasStruct {
+		fn serde(&mut self, data: &mut ReadableProfile) -> Result<(), DeserializationError> {
+			data.serde_enum_entry("external", &mut self.external, EnumTagging::External)?;
+			data.serde_enum_entry("unit", &mut self.unit, EnumTagging::External)?;
+			data.serde_enum_entry("internal", &mut self.internal, EnumTagging::Internal("type"))?;
+			data.serde_enum_entry("adjacent", &mut self.adjacent, EnumTagging::Adjacent { tag: "t", content: "c" })?;
+			data.serde_enum_entry("untagged", &mut self.untagged, EnumTagging::Untagged)
+		}
+	}
+
+	impl Serde<EfficientProfile> for CanvasStruct {
+		fn serde(&mut self, data: &mut EfficientProfile) -> Result<(), DeserializationError> {
+			data.serde_enum_item(&mut self.external)?;
+			data.serde_enum_item(&mut self.unit)?;
+			data.serde_enum_item(&mut self.internal)?;
+			data.serde_enum_item(&mut self.adjacent)?;
+			data.serde_enum_item(&mut self.untagged)
+		}
+	}
+
+	#[cfg(feature = "toml")]
+	impl TOMLSerde<ReadableProfile> for CanvasStruct {}
+	#[cfg(feature = "bin")]
+	impl BinSerde<EfficientProfile> for CanvasStruct {}
+	#[cfg(feature = "json")]
+	impl JSONSerde<ReadableProfile> for CanvasStruct { const TAB_SIZE: u16 = 4; }
+
+	fn make_canvas() -> CanvasStruct {
+		CanvasStruct {
+			external: ShapeEnum::Circle { radius: 1.5 },
+			unit: ShapeEnum::Empty,
+			internal: ShapeEnum::Rect { width: 3, height: 4 },
+			adjacent: ShapeEnum::Circle { radius: 2.0 },
+			untagged: ShapeEnum::Rect { width: 5, height: 6 },
+		}
+	}
+
+	/// Refuses to serialize levels above 9
+	#[derive(Default, Debug, PartialEq)]
+	struct GuardedStruct {
+		level: u8,
+	}
+
+	impl GuardedStruct {
+		fn check<P: DataProfile>(&self, data: &P) -> Result<(), DeserializationError> {
+			if data.is_serial() && self.level > 9 {
+				return Err(DeserializationError::NoMatch { field: "level".into(), actual: self.level.to_string() });
+			}
+			Ok(())
+		}
+	}
+
+	impl Serde<ReadableProfile> for GuardedStruct {
+		fn serde(&mut self, data: &mut ReadableProfile) -> Result<(), DeserializationError> {
+			self.check(data)?;
+			data.serde_entry("level", &mut self.level)
+		}
+	}
+
+	impl Serde<EfficientProfile> for GuardedStruct {
+		fn serde(&mut self, data: &mut EfficientProfile) -> Result<(), DeserializationError> {
+			self.check(data)?;
+			data.serde_item(&mut self.level)
+		}
+	}
+
+	#[derive(Default, Debug, PartialEq)]
+	struct VaultStruct {
+		guarded: GuardedStruct,
+	}
+
+	impl Serde<ReadableProfile> for VaultStruct {
+		fn serde(&mut self, data: &mut ReadableProfile) -> Result<(), DeserializationError> {
+			data.serde_nested_entry("guarded", &mut self.guarded)
+		}
+	}
+
+	impl Serde<EfficientProfile> for VaultStruct {
+		fn serde(&mut self, data: &mut EfficientProfile) -> Result<(), DeserializationError> {
+			data.serde_nested_item(&mut self.guarded)
+		}
+	}
+
+	#[cfg(feature = "toml")]
+	impl TOMLSerde<ReadableProfile> for VaultStruct {}
+	#[cfg(feature = "bin")]
+	impl BinSerde<EfficientProfile> for VaultStruct {}
+	#[cfg(feature = "json")]
+	impl JSONSerde<ReadableProfile> for VaultStruct { const TAB_SIZE: u16 = 4; }
+
+	fn make_vault(level: u8) -> VaultStruct {
+		VaultStruct { guarded: GuardedStruct { level } }
+	}
+
+	/// Fails to serialize after its first item
+	#[cfg(feature = "bin")]
+	#[derive(Default, Debug)]
+	struct HaltedStruct {
+		status: String,
+		vault: VaultStruct,
+	}
+
+	#[cfg(feature = "bin")]
+	impl Serde<EfficientProfile> for HaltedStruct {
+		fn serde(&mut self, data: &mut EfficientProfile) -> Result<(), DeserializationError> {
+			data.serde_item(&mut self.status)?;
+			data.serde_nested_item(&mut self.vault)
+		}
+	}
+
+	#[cfg(feature = "bin")]
+	impl BinSerde<EfficientProfile> for HaltedStruct {}
+
+	#[cfg(feature = "bin")]
+	fn make_halted() -> HaltedStruct {
+		HaltedStruct { status: "ok".into(), vault: make_vault(10) }
+	}
+
+	/// Writes a sequence that mixes a table with a number, which TOML cannot express
+	#[cfg(feature = "toml")]
+	#[derive(Default, Debug)]
+	struct RaggedStruct {
+		width: u8,
+	}
+
+	#[cfg(feature = "toml")]
+	impl Serde<ReadableProfile> for RaggedStruct {
+		fn serde(&mut self, data: &mut ReadableProfile) -> Result<(), DeserializationError> {
+			let mut rows = ArrayData::serial_ready();
+			rows.serialize_item(Datum::Map(MappedData::serial_ready()));
+			rows.serialize_item(self.width);
+			data.serialize_entry("rows", Datum::Array(rows));
+			Ok(())
+		}
+	}
+
+	#[cfg(feature = "toml")]
+	impl SerializeRef<ReadableProfile> for RaggedStruct {
+		fn serialize_fields(&self, data: &mut ReadableProfile) {
+			let mut rows = ArrayData::serial_ready();
+			rows.serialize_item(Datum::Map(MappedData::serial_ready()));
+			rows.serialize_item(self.width);
+			data.serialize_entry("rows", Datum::Array(rows));
+		}
+	}
+
+	#[cfg(feature = "toml")]
+	impl TOMLSerde<ReadableProfile> for RaggedStruct {}
+
+	/// Has no sensible default, as the name may not be empty
+	#[derive(Debug, PartialEq)]
+	struct AccountStruct {
+		name: String,
+		balance: i64,
+	}
+
+	impl AccountStruct {
+		fn new(name: String, balance: i64) -> Result<Self, DeserializationError> {
+			if name.is_empty() {
+				return Err(DeserializationError::NoMatch { field: "name".into(), actual: name });
+			}
+			Ok(Self { name, balance })
+		}
+	}
+
+	impl Serde<ReadableProfile> for AccountStruct {
+		fn serde(&mut self, data: &mut ReadableProfile) -> Result<(), DeserializationError> {
+			data.serde_entry("name", &mut self.name)?;
+			data.serde_entry("balance", &mut self.balance)
+		}
+	}
+
+	impl Serde<EfficientProfile> for AccountStruct {
+		fn serde(&mut self, data: &mut EfficientProfile) -> Result<(), DeserializationError> {
+			data.serde_item(&mut self.name)?;
+			data.serde_item(&mut self.balance)
+		}
+	}
+
+	impl DeserializeNew<ReadableProfile> for AccountStruct {
+		fn deserialize_fields(data: &mut ReadableProfile) -> Result<Self, DeserializationError> {
+			let mut name = String::new();
+			let mut balance = 0;
+			data.deserialize_entry("name", &mut name)?;
+			data.deserialize_entry("balance", &mut balance)?;
+			Self::new(name, balance)
+		}
+	}
+
+	impl DeserializeNew<EfficientProfile> for AccountStruct {
+		fn deserialize_fields(data: &mut EfficientProfile) -> Result<Self, DeserializationError> {
+			let mut name = String::new();
+			let mut balance = 0;
+			data.deserialize_item(&mut name)?;
+			data.deserialize_item(&mut balance)?;
+			Self::new(name, balance)
+		}
+	}
+
+	#[cfg(feature = "toml")]
+	impl TOMLSerde<ReadableProfile> for AccountStruct {}
+	#[cfg(feature = "bin")]
+	impl BinSerde<EfficientProfile> for AccountStruct {}
+	#[cfg(feature = "json")]
+	impl JSONSerde<ReadableProfile> for AccountStruct { const TAB_SIZE: u16 = 4; }
+
+	fn make_account() -> AccountStruct {
+		AccountStruct::new("ferus".into(), -52).unwrap()
+	}
+
+	#[cfg(feature = "bin")]
+	make_data_profile!(
+		/// A profile for testing little-endian layouts
+		LittleProfile use ArrayData { byte_order: ByteOrder::LittleEndian }
+	);
+
+	#[cfg(feature = "bin")]
+	#[derive(Default, Debug, PartialEq, Clone)]
+	struct SensorStruct {
+		id: u32,
+		temperature: f32,
+		flags: u16,
+		offset: i64,
+	}
+
+	#[cfg(feature = "bin")]
+	impl Serde<LittleProfile> for SensorStruct {
+		fn serde(&mut self, data: &mut LittleProfile) -> Result<(), DeserializationError> {
+			data.serde_item(&mut self.id)?;
+			data.serde_item(&mut self.temperature)?;
+			data.serde_item(&mut self.flags)?;
+			data.serde_item(&mut self.offset)
+		}
+	}
+
+	#[cfg(feature = "bin")]
+	impl BinSerde<LittleProfile> for SensorStruct {}
+
+	#[cfg(feature = "bin")]
+	make_data_profile!(
+		/// A profile for testing data that describes its own types
+		TaggedProfile use ArrayData { tagged: true }
+	);
+
+	#[cfg(feature = "bin")]
+	#[derive(Default, Debug, PartialEq, Clone)]
+	struct ReadingStruct {
+		sensor: String,
+		value: f64,
+		count: u32,
+	}
+
+	#[cfg(feature = "bin")]
+	impl Serde<TaggedProfile> for ReadingStruct {
+		fn serde(&mut self, data: &mut TaggedProfile) -> Result<(), DeserializationError> {
+			data.serde_item(&mut self.sensor)?;
+			data.serde_item(&mut self.value)?;
+			data.serde_item(&mut self.count)
+		}
+	}
+
+	#[cfg(feature = "bin")]
+	impl BinSerde<TaggedProfile> for ReadingStruct {}
+
+	/// Reads ReadingStruct without the value, to test mismatched types
+	#[cfg(feature = "bin")]
+	#[derive(Default, Debug)]
+	struct MisreadStruct {
+		sensor: String,
+		count: u32,
+	}
+
+	#[cfg(feature = "bin")]
+	impl Serde<TaggedProfile> for MisreadStruct {
+		fn serde(&mut self, data: &mut TaggedProfile) -> Result<(), DeserializationError> {
+			data.serde_item(&mut self.sensor)?;
+			data.serde_item(&mut self.count)
+		}
+	}
+
+	#[cfg(feature = "bin")]
+	impl BinSerde<TaggedProfile> for MisreadStruct {}
+
+	make_data_profile!(
+		/// A profile for testing data that records the version of every value
+		VersionedProfile use ArrayData { versioned: true }
+	);
+
+	/// The email was added in version 1
+	#[derive(Default, Debug, PartialEq, Clone)]
+	struct ContactStruct {
+		name: String,
+		email: String,
+	}
+
+	impl Serde<ReadableProfile> for ContactStruct {
+		const VERSION: u8 = 1;
+
+		fn serde(&mut self, data: &mut ReadableProfile) -> Result<(), DeserializationError> {
+			data.serde_entry("name", &mut self.name)?;
+			if data.version() >= 1 {
+				data.serde_entry("email", &mut self.email)?;
+			}
+			Ok(())
+		}
+		fn migrate(&mut self, from_version: u8) -> Result<(), DeserializationError> {
+			if from_version == 0 {
+				self.email = format!("{}@example.com", self.name.to_lowercase());
+			}
+			Ok(())
+		}
+	}
+
+	impl SerializeRef<ReadableProfile> for ContactStruct {
+		fn serialize_fields(&self, data: &mut ReadableProfile) {
+			data.serialize_entry("name", &self.name);
+			data.serialize_entry("email", &self.email);
+		}
+	}
+
+	impl Serde<VersionedProfile> for ContactStruct {
+		const VERSION: u8 = 1;
+
+		fn serde(&mut self, data: &mut VersionedProfile) -> Result<(), DeserializationError> {
+			data.serde_item(&mut self.name)?;
+			if data.version() >= 1 {
+				data.serde_item(&mut self.email)?;
+			}
+			Ok(())
+		}
+		fn migrate(&mut self, from_version: u8) -> Result<(), DeserializationError> {
+			if from_version == 0 {
+				self.email = format!("{}@example.com", self.name.to_lowercase());
+			}
+			Ok(())
+		}
+	}
+
+	#[derive(Default, Debug, PartialEq)]
+	struct AddressBookStruct {
+		contacts: Vec<ContactStruct>,
+	}
+
+	impl Serde<VersionedProfile> for AddressBookStruct {
+		fn serde(&mut self, data: &mut VersionedProfile) -> Result<(), DeserializationError> {
+			data.serde_nested_seq_item(&mut self.contacts)
+		}
+	}
+
+	make_data_profile!(
+		/// A profile for testing files that leave out default values
+		MinimalProfile use MappedData { skip_defaults: true }
+	);
+
+	#[derive(Default, Debug, PartialEq, Clone)]
+	struct ServerStruct {
+		host: String,
+		port: u16,
+		verbose: bool,
+	}
+
+	impl Serde<ReadableProfile> for ServerStruct {
+		fn serde(&mut self, data: &mut ReadableProfile) -> Result<(), DeserializationError> {
+			data.serde_entry_or("host", &mut self.host, "localhost".into())?;
+			data.serde_entry_or("port", &mut self.port, 8080)?;
+			data.serde_entry_or_default("verbose", &mut self.verbose)
+		}
+	}
+
+	impl Serde<MinimalProfile> for ServerStruct {
+		fn serde(&mut self, data: &mut MinimalProfile) -> Result<(), DeserializationError> {
+			data.serde_entry_or("host", &mut self.host, "localhost".into())?;
+			data.serde_entry_or("port", &mut self.port, 8080)?;
+			data.serde_entry_or_default("verbose", &mut self.verbose)
+		}
+	}
+
+	make_data_profile!(
+		/// A profile for testing data that must not have unknown entries
+		StrictProfile use MappedData { deny_unknown: true }
+	);
+
+	#[derive(Default, Debug, PartialEq, Clone)]
+	struct SettingsStruct {
+		volume: u8,
+		extra: HashMap<String, Datum>,
+	}
+
+	impl Serde<ReadableProfile> for SettingsStruct {
+		fn serde(&mut self, data: &mut ReadableProfile) -> Result<(), DeserializationError> {
+			data.serde_entry("volume", &mut self.volume)?;
+			data.serde_remaining_entries(&mut self.extra)
+		}
+	}
+
+	impl Serde<StrictProfile> for SettingsStruct {
+		fn serde(&mut self, data: &mut StrictProfile) -> Result<(), DeserializationError> {
+			data.serde_entry("volume", &mut self.volume)
+		}
+	}
+
+	#[derive(Default, Debug, PartialEq, Clone)]
+	struct MixerStruct {
+		main: SettingsStruct,
+	}
+
+	impl Serde<StrictProfile> for MixerStruct {
+		fn serde(&mut self, data: &mut StrictProfile) -> Result<(), DeserializationError> {
+			data.serde_nested_entry("main", &mut self.main)
+		}
+	}
+
+	impl Serde<StrictProfile> for ShapeEnum {
+		fn serde(&mut self, data: &mut StrictProfile) -> Result<(), DeserializationError> {
+			match self {
+				ShapeEnum::Circle { radius } => data.serde_entry("radius", radius),
+				ShapeEnum::Rect { width, height } => {
+					data.serde_entry("width", width)?;
+					data.serde_entry("height", height)
+				}
+				ShapeEnum::Empty => Ok(())
+			}
+		}
+	}
+
+	#[derive(Default, Debug, PartialEq)]
+	struct PlotStruct {
+		external: ShapeEnum,
+		adjacent: ShapeEnum,
+	}
+
+	impl Serde<StrictProfile> for PlotStruct {
+		fn serde(&mut self, data: &mut StrictProfile) -> Result<(), DeserializationError> {
+			data.serde_enum_entry("external", &mut self.external, EnumTagging::External)?;
+			data.serde_enum_entry("adjacent", &mut self.adjacent, EnumTagging::Adjacent { tag: "t", content: "c" })
+		}
+	}
+
+	#[derive(Default, Debug, PartialEq, Clone)]
+	struct CountryStruct {
+		code: String,
+		name: String,
+	}
+
+	impl Serde<EfficientProfile> for CountryStruct {
+		fn serde(&mut self, data: &mut EfficientProfile) -> Result<(), DeserializationError> {
+			data.serde_item_sized(&mut self.code, DatumSize::U8)?;
+			data.serde_item(&mut self.name)
+		}
+	}
+
+	#[cfg(feature = "bin")]
+	impl BinSerde<EfficientProfile> for CountryStruct {}
+
+	/// Reports a variant index that does not fit in the size used for the indices of its variants
+	#[derive(Default, Debug)]
+	struct StrayEnum;
+
+	impl EnumVariants for StrayEnum {
+		const VARIANTS: &'static [&'static str] = &["only"];
+
+		fn variant_index(&self) -> usize {
+			256
+		}
+		fn from_variant_index(index: usize) -> Option<Self> {
+			(index == 0).then_some(StrayEnum)
+		}
+	}
+
+	impl Serde<EfficientProfile> for StrayEnum {
+		fn serde(&mut self, _data: &mut EfficientProfile) -> Result<(), DeserializationError> {
+			Ok(())
+		}
+	}
+
+	#[derive(Default, Debug)]
+	struct StrayStruct {
+		stray: StrayEnum,
+	}
+
+	impl Serde<EfficientProfile> for StrayStruct {
+		fn serde(&mut self, data: &mut EfficientProfile) -> Result<(), DeserializationError> {
+			data.serde_enum_item(&mut self.stray)
+		}
+	}
+
+	#[cfg(feature = "bin")]
+	impl BinSerde<EfficientProfile> for StrayStruct {}
+
+	#[cfg(feature = "bin")]
+	make_data_profile!(
+		/// A profile for testing variable-length integers
+		VarintProfile use ArrayData { pack_bools: true, varint: true }
+	);
+
+	#[cfg(feature = "bin")]
+	#[derive(Default, Debug, PartialEq, Clone)]
+	struct CounterStruct {
+		hits: u64,
+		port: u16,
+		delta: i32,
+		offset: i64,
+		label: String,
+		samples: Vec<u32>,
+	}
+
+	#[cfg(feature = "bin")]
+	impl Serde<VarintProfile> for CounterStruct {
+		fn serde(&mut self, data: &mut VarintProfile) -> Result<(), DeserializationError> {
+			data.serde_item(&mut self.hits)?;
+			data.serde_item(&mut self.port)?;
+			data.serde_item(&mut self.delta)?;
+			data.serde_item(&mut self.offset)?;
+			data.serde_item(&mut self.label)?;
+			data.serde_seq_item(&mut self.samples)
+		}
+	}
+
+	#[cfg(feature = "bin")]
+	impl BinSerde<VarintProfile> for CounterStruct {}
+
+	#[cfg(feature = "derive")]
+	#[derive(Serde, Default, Debug, PartialEq)]
+	struct DerivedStruct {
+		name: String,
+		#[mangle(rename = "years")]
+		age: u64,
+		#[mangle(skip)]
+		cache: u32,
+		#[mangle(default)]
+		nickname: String,
+		#[mangle(profile = EfficientProfile, skip)]
+		comment: String,
+	}
+
+	#[cfg(feature = "derive")]
+	#[derive(Serde, Default, Debug, PartialEq)]
+	#[mangle(profile = ReadableProfile)]
+	struct DerivedTuple(String, u8);
+
+	#[cfg(all(feature = "derive", any(feature = "toml", feature = "bin")))]
+	#[derive(Serde, Default, Debug, PartialEq)]
+	struct DerivedKennelStruct {
+		keeper: Option<String>,
+		tags: Vec<String>,
+		scores: HashMap<String, u32>,
+		#[mangle(nested)]
+		mascot: PetStruct,
+		#[mangle(nested)]
+		pets: Vec<PetStruct>,
+		#[mangle(nested, default)]
+		rooms: BTreeMap<u8, PetStruct>,
+		#[mangle(enum)]
+		yard: ShapeEnum,
+	}
+
+	#[cfg(all(feature = "derive", feature = "toml"))]
+	impl TOMLSerde<ReadableProfile> for DerivedStruct {}
+	#[cfg(all(feature = "derive", feature = "toml"))]
+	impl TOMLSerde<ReadableProfile> for DerivedKennelStruct {}
+	#[cfg(all(feature = "derive", feature = "bin"))]
+	impl BinSerde<EfficientProfile> for DerivedKennelStruct {}
+	#[cfg(all(feature = "derive", feature = "bin"))]
+	impl BinSerde<EfficientProfile> for DerivedStruct {}
+
+	#[cfg(feature = "derive")]
+	fn make_derived() -> DerivedStruct {
+		DerivedStruct {
+			name: "ferus".into(),
+			age: 52,
+			cache: 0,
+			nickname: "fe".into(),
+			comment: "hello".into(),
+		}
+	}
+
+	#[cfg(all(feature = "derive", any(feature = "toml", feature = "bin")))]
+	fn make_kennel() -> DerivedKennelStruct {
+		let pet = |species: &str, legs| PetStruct {
+			species: species.into(),
+			legs,
+			vaccinated: true,
+		};
+		DerivedKennelStruct {
+			keeper: Some("ferus".into()),
+			tags: vec!["dogs".into(), "cats".into()],
+			scores: [("cleanliness".to_string(), 4), ("space".to_string(), 5)].into_iter().collect(),
+			mascot: pet("parrot", 2),
+			pets: vec![pet("dog", 4), pet("cat", 4)],
+			rooms: [(1, pet("dog", 3)), (2, pet("snake", 0))].into_iter().collect(),
+			yard: ShapeEnum::Rect { width: 10, height: 20 },
+		}
+	}
+
+	/// Serializes into a ReadableProfile and deserializes back out of a HashMap
+	fn map_roundtrip<T: Serde<ReadableProfile> + Default>(src: T) -> T {
+		let map: HashMap<String, Datum> = src.into_data_profile().0.into_serialized_entries()
+			.map(|(name, value)| (name.to_key_string(), value))
+			.collect();
+		T::from_data_profile(ProfileFromData::try_from(map).unwrap()).unwrap()
+	}
+
+	/// What a value was written as in each enabled format
+	struct Written {
+		#[cfg(feature = "toml")]
+		toml: String,
+		#[cfg(feature = "json")]
+		json: String,
+		#[cfg(feature = "bin")]
+		bin: Vec<u8>,
+	}
+
+	/// Writes a value in each enabled format, and passes what each of them reads back to check.
+	/// Returns what was written, so that tests can check the formats themselves
+	#[cfg_attr(not(any(feature = "toml", feature = "json", feature = "bin")), allow(unused_variables))]
+	fn roundtrip<T>(make: fn() -> T, check: impl Fn(T)) -> Written
+		where T: Serde<ReadableProfile> + Serde<EfficientProfile> + Default
+	{
+		#[cfg(feature = "toml")]
+		let toml = extern_toml::to_string(&Serde::<ReadableProfile>::serialize::<extern_toml::Value>(make())).unwrap();
+		#[cfg(feature = "toml")]
+		check(Serde::<ReadableProfile>::deserialize::<extern_toml::Value>(toml.parse().unwrap()).unwrap());
+		#[cfg(feature = "json")]
+		let json = extern_json::stringify(Serde::<ReadableProfile>::serialize::<extern_json::JsonValue>(make()));
+		#[cfg(feature = "json")]
+		check(Serde::<ReadableProfile>::deserialize::<extern_json::JsonValue>(extern_json::parse(&json).unwrap()).unwrap());
+		#[cfg(feature = "bin")]
+		let bin = Serde::<EfficientProfile>::serialize::<Vec<u8>>(make());
+		#[cfg(feature = "bin")]
+		check(Serde::<EfficientProfile>::deserialize::<Vec<u8>>(bin.clone()).unwrap());
+		Written {
+			#[cfg(feature = "toml")]
+			toml,
+			#[cfg(feature = "json")]
+			json,
+			#[cfg(feature = "bin")]
+			bin,
+		}
+	}
+
+	#[test]
+	fn test_hashmap_source() {
+		let src = TestStruct {
+			name: "ferus",
+			age: 52,
+			id: "gangnam".into(),
+		};
+		let test: TestProfile = convert_data_profile(Serde::<ReadableProfile>::into_data_profile(src));
+		let map: HashMap<String, Datum> = test.0.into_serialized_entries()
+			.map(|(name, value)| (name.to_key_string(), value))
+			.collect();
+		let deser: TestStruct = Serde::<ReadableProfile>::from_data_profile(ProfileFromData::try_from(map).unwrap()).unwrap();
+		assert_eq!(deser.age, 52);
+		assert_eq!(deser.id, "gangnam");
+	}
+
+	#[test]
+	fn test_signed() {
+		let _written = roundtrip(make_signed, |deser| assert_eq!(deser, make_signed()));
+		#[cfg(feature = "bin")]
+		assert_eq!(_written.bin.len(), 1 + 2 + 4 + 8);
+		assert_eq!(map_roundtrip(make_signed()), make_signed());
+	}
+
+	#[test]
+	fn test_float() {
+		let _written = roundtrip(make_float, assert_float_eq);
+		#[cfg(feature = "bin")]
+		assert_eq!(_written.bin.len(), 4 + 8 + 8 + 4);
+		assert_float_eq(map_roundtrip(make_float()));
+		// An f32 is written in its shortest form, and floats written by hand are rounded to the nearest f32
+		#[cfg(feature = "toml")]
+		{
+			assert!(_written.toml.contains("ratio = 0.1\n"));
+			assert_eq!(FloatStruct::deserialize_toml("ratio = 0.1\nlatitude = 0.0\nmissing = 0.0\nlimit = 1e30").unwrap().ratio, 0.1);
+		}
+		#[cfg(feature = "json")]
+		{
+			let ser = &_written.json;
+			assert!(ser.contains("\"ratio\":0.1,") || ser.ends_with("\"ratio\":0.1}"));
+			assert_eq!(FloatStruct::deserialize_json(r#"{"ratio":0.1,"latitude":0,"missing":0,"limit":1e30}"#).unwrap().ratio, 0.1);
+		}
+		assert_eq!(f32::try_from(Datum::F64(0.1)).unwrap(), 0.1);
+		assert_eq!(f32::try_from(Datum::F64(f64::INFINITY)).unwrap(), f32::INFINITY);
+		assert_eq!(f32::try_from(Datum::U32(16_777_217)).unwrap(), 16_777_216.0);
+		// Finite floats that are too large for an f32 do not become infinity
+		assert!(f32::try_from(Datum::F64(1e300)).is_err());
+	}
+
+	#[test]
+	fn test_bool() {
+		let _written = roundtrip(make_flags, |deser| assert_eq!(deser, make_flags()));
+		#[cfg(feature = "bin")]
+		{
+			assert_eq!(_written.bin, vec![0b101, 3, 1]);
+			let ser: Vec<u8> = Serde::<LooseProfile>::serialize(make_flags());
+			assert_eq!(ser, vec![1, 0, 1, 3, 1]);
+			assert_eq!(<FlagStruct as Serde<LooseProfile>>::deserialize(ser).unwrap(), make_flags());
+		}
+		assert_eq!(map_roundtrip(make_flags()), make_flags());
+		assert_eq!(Serde::<EfficientProfile>::into_data_profile(make_flags()).0.into_serialized_items().count(), 3);
+		assert_eq!(Serde::<LooseProfile>::into_data_profile(make_flags()).0.into_serialized_items().count(), 5);
+	}
+
+	#[test]
+	fn test_optional() {
+		let _written = roundtrip(make_optional, |deser| assert_eq!(deser, make_optional()));
+		#[cfg(feature = "toml")]
+		assert!(!_written.toml.contains("nickname"));
+		#[cfg(feature = "json")]
+		{
+			let deser = OptionalStruct::deserialize_json(r#"{"nickname": null, "age": null}"#).unwrap();
+			assert_eq!(deser, OptionalStruct::default());
+		}
+		// Both presence flags are packed into the first byte
+		#[cfg(feature = "bin")]
+		assert_eq!(_written.bin, vec![0b10, 0, 0, 0, 0, 0, 0, 0, 52]);
+		assert_eq!(map_roundtrip(make_optional()), make_optional());
+	}
+
+	#[test]
+	fn test_nested() {
+		let _written = roundtrip(make_owner, |deser| assert_eq!(deser, make_owner()));
+		#[cfg(feature = "toml")]
+		assert!(_written.toml.contains("[pet]"));
+		#[cfg(feature = "json")]
+		assert!(_written.json.contains(r#""pet":{"#));
+		#[cfg(feature = "bin")]
+		assert_eq!(_written.bin, vec![0, 0, 0, 5, b'f', b'e', b'r', b'u', b's', 0, 0, 0, 3, b'c', b'a', b't', 4, 1, 0]);
+		let data = Serde::<EfficientProfile>::into_data_profile(make_owner());
+		assert_eq!(data.0.into_serialized_items().count(), 5);
+	}
+
+	#[test]
+	fn test_serialize_ref() {
+		let owner = make_owner();
+		let shelter = make_shelter();
+		let name = String::from("ferus");
+		assert_eq!(Datum::from(&name), Datum::from("ferus"));
+		assert_eq!(Datum::from(&52u64), Datum::U64(52));
+		#[cfg(feature = "toml")]
+		{
+			assert_eq!(owner.serialize_toml_ref(), make_owner().serialize_toml());
+			assert_eq!(ShelterStruct::deserialize_toml(shelter.serialize_toml_ref()).unwrap(), make_shelter());
+		}
+		#[cfg(feature = "json")]
+		{
+			assert_eq!(OwnerStruct::deserialize_json(owner.serialize_json_ref()).unwrap(), make_owner());
+			assert_eq!(ShelterStruct::deserialize_json(shelter.serialize_json_ref()).unwrap(), make_shelter());
+		}
+		#[cfg(feature = "bin")]
+		{
+			assert_eq!(owner.serialize_bin_ref(), make_owner().serialize_bin());
+			assert_eq!(shelter.serialize_bin_ref(), make_shelter().serialize_bin());
+		}
+		#[cfg(all(feature = "derive", feature = "toml"))]
+		{
+			let derived = make_derived();
+			assert_eq!(derived.serialize_toml_ref(), make_derived().serialize_toml());
+			assert_eq!(derived, make_derived());
+		}
+		let data = SerializeRef::<EfficientProfile>::to_data_profile(&shelter);
+		assert_eq!(data.0.into_serialized_items().count(), 3 + 2 + 3 + 6);
+		assert_eq!(owner, make_owner());
+		assert_eq!(shelter, make_shelter());
+	}
+
+	#[test]
+	fn test_deserialize_without_default() {
+		#[cfg(feature = "toml")]
+		{
+			assert_eq!(AccountStruct::deserialize_toml_new(make_account().serialize_toml()).unwrap(), make_account());
+			assert!(AccountStruct::deserialize_toml_new("name = \"\"\nbalance = 3").is_err());
+			let mut account = make_account();
+			account.deserialize_toml_into("name = \"gangnam\"\nbalance = 3").unwrap();
+			assert_eq!(account, AccountStruct::new("gangnam".into(), 3).unwrap());
+		}
+		#[cfg(feature = "json")]
+		{
+			assert_eq!(AccountStruct::deserialize_json_new(make_account().serialize_json()).unwrap(), make_account());
+			let mut account = make_account();
+			account.deserialize_json_into(r#"{"name":"gangnam","balance":3}"#).unwrap();
+			assert_eq!(account.balance, 3);
+		}
+		#[cfg(feature = "bin")]
+		{
+			assert_eq!(AccountStruct::deserialize_bin_new(make_account().serialize_bin()).unwrap(), make_account());
+			let mut account = AccountStruct::new("gangnam".into(), 3).unwrap();
+			account.deserialize_bin_into(make_account().serialize_bin()).unwrap();
+			assert_eq!(account, make_account());
+		}
+		let map = HashMap::from([("name".to_string(), Datum::from("")), ("balance".to_string(), Datum::from(3i64))]);
+		assert!(<AccountStruct as DeserializeNew<ReadableProfile>>::deserialize_new(map.clone()).is_err());
+		let mut account = make_account();
+		Serde::<ReadableProfile>::deserialize_into(&mut account, map).unwrap();
+		assert_eq!(account.name, "");
+	}
+
+	#[test]
+	fn test_serialization_error() {
+		#[cfg(feature = "toml")]
+		{
+			assert_eq!(VaultStruct::deserialize_toml(make_vault(3).try_serialize_toml().unwrap()).unwrap(), make_vault(3));
+			assert!(matches!(make_vault(10).try_serialize_toml(), Err(SerializationError::Serde { .. })));
+			match (RaggedStruct { width: 3 }).try_serialize_toml_ref() {
+				Err(SerializationError::TOMLError { field, .. }) => assert_eq!(field, "rows[1]"),
+				x => panic!("{:?}", x)
+			}
+			assert!(RaggedStruct::default().try_serialize_toml().is_err());
+			assert!(std::panic::catch_unwind(|| RaggedStruct::default().serialize_toml()).is_err());
+		}
+		#[cfg(feature = "json")]
+		{
+			assert_eq!(VaultStruct::deserialize_json(make_vault(3).try_serialize_json().unwrap()).unwrap(), make_vault(3));
+			assert!(make_vault(10).try_serialize_json().is_err());
+		}
+		#[cfg(feature = "bin")]
+		{
+			assert_eq!(make_vault(3).try_serialize_bin().unwrap(), vec![3]);
+			assert!(make_vault(10).try_serialize_bin().is_err());
+		}
+		match Serde::<ReadableProfile>::try_into_data_profile(make_vault(10)) {
+			Err(SerializationError::Serde { field, error: DeserializationError::NoMatch { actual, .. } }) => {
+				assert_eq!(field, "guarded.level");
+				assert_eq!(actual, "10");
+			}
+			x => panic!("{:?}", x)
+		}
+		assert!(Serde::<EfficientProfile>::try_into_data_profile(make_vault(3)).is_ok());
+	}
+
+	#[test]
+	fn test_field_path() {
+		let map = HashMap::from([
+			("name".to_string(), Datum::from("ferus")),
+			("age".to_string(), Datum::from("old")),
+			("id".to_string(), Datum::from("gangnam")),
+		]);
+		match Serde::<ReadableProfile>::from_data_profile(ProfileFromData::try_from(map).unwrap()) {
+			Err::<TestStruct, _>(DeserializationError::InvalidType { field, expected, actual }) => {
+				assert_eq!(field, "age");
+				assert_eq!(expected, "u64");
+				assert_eq!(actual, "String");
+			}
+			x => panic!("{:?}", x)
+		}
+		let items = VecDeque::from([Datum::from("ferus"), Datum::from("old")]);
+		match Serde::<EfficientProfile>::from_data_profile(ProfileFromData::try_from(items).unwrap()) {
+			Err::<TestStruct, _>(DeserializationError::InvalidType { field, actual, .. }) => {
+				assert_eq!(field, "[1]");
+				assert_eq!(actual, "String");
+			}
+			x => panic!("{:?}", x)
+		}
+		#[cfg(feature = "toml")]
+		match ShelterStruct::deserialize_toml(make_shelter().serialize_toml().replace("legs = 0", "legs = \"none\"")) {
+			Err(DeserializationError::InvalidType { field, actual, .. }) => {
+				assert_eq!(field, "pets[1].legs");
+				assert_eq!(actual, "String");
+			}
+			x => panic!("{:?}", x)
+		}
+		#[cfg(feature = "json")]
+		match DirectoryStruct::deserialize_json(make_directory().serialize_json().replace(r#""legs":4"#, r#""legs":true"#)) {
+			Err(DeserializationError::InvalidType { field, actual, .. }) => {
+				assert_eq!(field, "pets.tom.legs");
+				assert_eq!(actual, "bool");
+			}
+			x => panic!("{:?}", x)
+		}
+		#[cfg(feature = "bin")]
+		{
+			let src = TestStruct {
+				name: "ferus",
+				age: 52,
+				id: "gangnam".into(),
+			};
+			let mut ser = src.serialize_bin();
+			// Corrupt the first character of the name
+			ser[4] = b'g';
+			assert!(matches!(
+				TestStruct::deserialize_bin(ser),
+				Err(DeserializationError::NoMatch { field, .. }) if field == "[0]"
+			));
+		}
+	}
+
+	#[test]
+	fn test_error_display() {
+		let error = DeserializationError::InvalidType { field: "age".into(), expected: "u64", actual: "String" };
+		assert_eq!(error.to_string(), "field `age`: expected u64, found String");
+		assert!(error.source().is_none());
+		let boxed: Box<dyn Error> = Box::new(DeserializationError::MissingField("pets[1].legs".into()));
+		assert_eq!(boxed.to_string(), "missing field `pets[1].legs`");
+		let utf8 = DeserializationError::FromUTF8Error(String::from_utf8(vec![0xff]).unwrap_err());
+		assert!(utf8.source().unwrap().is::<FromUtf8Error>());
+		let serialization = SerializationError::from(DeserializationError::NoMatch { field: "level".into(), actual: "10".into() });
+		assert_eq!(serialization.to_string(), "could not serialize field `level`: field `level`: 10 does not match any expected value");
+		assert!(serialization.source().unwrap().is::<DeserializationError>());
+		#[cfg(feature = "toml")]
+		{
+			let error = TestStruct::deserialize_toml("name = ").unwrap_err();
+			assert!(error.to_string().starts_with("invalid TOML: "));
+			assert!(error.source().unwrap().is::<TOMLError>());
+		}
+		#[cfg(feature = "json")]
+		{
+			let error = TestStruct::deserialize_json("{").unwrap_err();
+			assert!(error.to_string().starts_with("invalid JSON: "));
+			assert!(error.source().unwrap().is::<JSONError>());
+		}
+	}
+
+	#[test]
+	fn test_no_panics() {
+		let map = Datum::Map(Serde::<ReadableProfile>::into_data_profile(make_owner()).0);
+		let array = Datum::Array(Serde::<EfficientProfile>::into_data_profile(make_owner()).0);
+		assert_eq!(map, map.clone());
+		assert_eq!(array, array.clone());
+		assert_ne!(map, Datum::Map(Serde::<ReadableProfile>::into_data_profile(OwnerStruct::default()).0));
+		let keys = std::collections::HashSet::from([map.clone(), array.clone()]);
+		assert!(keys.contains(&map) && keys.contains(&array));
+		assert!(map.to_key_string().contains("name = ferus"));
+		assert!(array.to_key_string().starts_with("[ferus, "));
+		#[cfg(feature = "bin")]
+		{
+			let bytes: Vec<u8> = map.try_into().unwrap();
+			assert_eq!(&bytes[..4], &[0, 0, 0, 3]);
+			// Arrays read from binary cannot list their items, so they are only equal to themselves
+			let read = Datum::Array(ProfileFromData::try_from(BinCursor::new(vec![0, 0, 0, 1, b'a'])).unwrap());
+			let other = Datum::Array(ProfileFromData::try_from(BinCursor::new(vec![0, 0, 0, 1, b'b'])).unwrap());
+			assert_eq!(read, read);
+			assert_ne!(read, other);
+			assert_ne!(read, read.clone());
+			assert_ne!(read, array);
+			assert_eq!(read.to_key_string(), "[..]");
+			assert!(matches!(OwnerStruct::deserialize_bin(vec![0, 0, 0, 9, b'f']), Err(DeserializationError::UnexpectedEOF)));
+		}
+		#[cfg(feature = "toml")]
+		{
+			let test = TestStruct::deserialize_toml("name = \"ferus\"\nage = 52\nid = 1979-05-27").unwrap();
+			assert_eq!(test.id, "1979-05-27");
+			let data: MappedData = ProfileFromData::try_from("pet = { legs = 4 }".parse::<extern_toml::Value>().unwrap()).unwrap();
+			let value: extern_toml::Value = ProfileToData::into(data);
+			assert_eq!(value["pet"]["legs"].as_integer(), Some(4));
+		}
+		#[cfg(feature = "json")]
+		match ShelterStruct::deserialize_json(r#"{"tags":[null],"ratings":[],"pets":[]}"#) {
+			Err(DeserializationError::InvalidType { field, actual, .. }) => {
+				assert_eq!(field, "tags[0]");
+				assert_eq!(actual, "null");
+			}
+			x => panic!("{:?}", x)
+		}
+	}
+
+	#[test]
+	#[cfg(feature = "bin")]
+	fn test_bin_datum() {
+		let mut nested = MappedData::serial_ready();
+		nested.serialize_entry("pets", Datum::Array(Serde::<EfficientProfile>::into_data_profile(make_owner()).0));
+		nested.serialize_entry(7u16, -2.5f64);
+		let datums = vec![
+			Datum::from("ferus"),
+			Datum::from(true),
+			Datum::from(u8::MAX),
+			Datum::from(u16::MAX),
+			Datum::from(u32::MAX),
+			Datum::from(u64::MAX),
+			Datum::from(i8::MIN),
+			Datum::from(i16::MIN),
+			Datum::from(i32::MIN),
+			Datum::from(i64::MIN),
+			Datum::from(1.5f32),
+			Datum::from(f64::NAN),
+			Datum::Map(Serde::<ReadableProfile>::into_data_profile(make_owner()).0),
+			Datum::Array(Serde::<EfficientProfile>::into_data_profile(make_shelter()).0),
+			Datum::Map(nested),
+		];
+		for datum in datums {
+			let mut data = ArrayData::serial_ready();
+			data.serialize_item(datum.clone());
+			let bytes: Vec<u8> = ProfileToData::into(data);
+			let mut cursor = BinCursor::new(bytes);
+			assert_eq!(cursor.get_datum(datum.get_type(), DatumSize::U32).unwrap(), datum);
+			assert!(cursor.remaining_bytes().is_empty());
+		}
+		assert!(matches!(
+			BinCursor::new(vec![0, 0, 0, 1, 14]).get_datum(DatumType::Array, DatumSize::U32),
+			Err(DeserializationError::NoMatch { field, .. }) if field == "[0]"
+		));
+	}
+
+	#[test]
+	#[cfg(feature = "bin")]
+	fn test_bin_cursor() {
+		static OWNER: &[u8] = &[0, 0, 0, 5, b'f', b'e', b'r', b'u', b's', 0, 0, 0, 3, b'c', b'a', b't', 4, 1, 0];
+		assert_eq!(OwnerStruct::deserialize_bin_buffer(OWNER).unwrap(), make_owner());
+		let shared: std::sync::Arc<[u8]> = OWNER.into();
+		assert_eq!(OwnerStruct::deserialize_bin_buffer(shared).unwrap(), make_owner());
+		let mut cursor = BinCursor::new(OWNER);
+		assert_eq!(cursor.get_datum(DatumType::String, DatumSize::U32).unwrap(), Datum::from("ferus"));
+		assert_eq!(cursor.offset(), 9);
+		assert_eq!(cursor.remaining_bytes().len(), OWNER.len() - 9);
+		assert!(matches!(cursor.get_datum(DatumType::String, DatumSize::U64), Err(DeserializationError::UnexpectedEOF)));
+		// Strings can be borrowed from slices instead of being copied
+		let mut cursor = BinCursor::new(OWNER);
+		let name: &'static str = cursor.next_str(DatumSize::U32).unwrap();
+		assert_eq!(name, "ferus");
+		assert_eq!(name.as_ptr(), OWNER[4..].as_ptr());
+		assert_eq!(cursor.next_str(DatumSize::U32).unwrap(), "cat");
+		assert_eq!(cursor.next_slice(3).unwrap(), &[4, 1, 0]);
+		assert!(matches!(cursor.next_slice(1), Err(DeserializationError::UnexpectedEOF)));
+		assert!(matches!(BinCursor::new(&[0, 0, 0, 1, 0xff][..]).next_str(DatumSize::U32), Err(DeserializationError::FromUTF8Error(_))));
+		let shelter = ShelterStruct {
+			tags: (0..10_000).map(|n| n.to_string()).collect(),
+			ratings: (0..10_000).collect(),
+			pets: Vec::new(),
+		};
+		let ser = shelter.serialize_bin_ref();
+		assert_eq!(ShelterStruct::deserialize_bin(ser).unwrap(), shelter);
+	}
+
+	#[test]
+	fn test_io() {
+		#[cfg(feature = "bin")]
+		{
+			/// Hands out one byte per read, like a slow socket
+			struct Trickle(std::vec::IntoIter<u8>);
+			impl std::io::Read for Trickle {
+				fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+					match (self.0.next(), buf.first_mut()) {
+						(Some(byte), Some(first)) => {
+							*first = byte;
+							Ok(1)
+						}
+						_ => Ok(0)
+					}
+				}
+			}
+			// Several values can be written to and read from the same stream, one after another
+			let writer = BinWriter::new(Vec::new());
+			make_shelter().serialize_bin_to(&writer).unwrap();
+			make_flags().serialize_bin_to(&writer).unwrap();
+			let out = writer.into_inner().unwrap();
+			assert_eq!(out, [make_shelter().serialize_bin(), make_flags().serialize_bin()].concat());
+			let reader = BinReader::new(Trickle(out.into_iter()));
+			assert_eq!(ShelterStruct::deserialize_bin_from(&reader).unwrap(), make_shelter());
+			assert_eq!(FlagStruct::deserialize_bin_from(&reader).unwrap(), make_flags());
+			assert_eq!(reader.into_inner().unwrap().0.len(), 0);
+			let reader = BinReader::new(Trickle(vec![0, 0, 0, 2, 0].into_iter()));
+			assert!(matches!(ShelterStruct::deserialize_bin_from(&reader), Err(DeserializationError::UnexpectedEOF)));
+			// Items are written as soon as they are serialized, so the items before an error are already written
+			let writer = BinWriter::new(Vec::new());
+			assert!(matches!(make_halted().serialize_bin_to(&writer), Err(SerializationError::Serde { .. })));
+			assert_eq!(writer.into_inner().unwrap(), [0, 0, 0, 2, b'o', b'k']);
+		}
+		#[cfg(feature = "toml")]
+		{
+			let mut out = Vec::new();
+			make_owner().serialize_toml_to(&mut out).unwrap();
+			assert_eq!(OwnerStruct::deserialize_toml_from(out.as_slice()).unwrap(), make_owner());
+			assert!(matches!(OwnerStruct::deserialize_toml_from([0xff].as_slice()), Err(DeserializationError::IOError(_))));
+		}
+		#[cfg(feature = "json")]
+		{
+			let mut out = Vec::new();
+			make_directory().serialize_json_pretty_to(&mut out).unwrap();
+			assert_eq!(DirectoryStruct::deserialize_json_from(out.as_slice()).unwrap(), make_directory());
+		}
+	}
+
+	#[test]
+	#[cfg(feature = "bin")]
+	fn test_varint() {
+		let small = CounterStruct {
+			hits: 3,
+			port: 80,
+			delta: -2,
+			offset: 64,
+			label: "ok".into(),
+			samples: vec![1, 300],
+		};
+		let ser = small.clone().serialize_bin();
+		assert_eq!(ser, vec![3, 80, 3, 0x80, 0x01, 2, b'o', b'k', 2, 1, 0xac, 0x02]);
+		assert_eq!(CounterStruct::deserialize_bin(ser).unwrap(), small);
+		let large = CounterStruct {
+			hits: u64::MAX,
+			port: u16::MAX,
+			delta: i32::MIN,
+			offset: i64::MIN,
+			label: "x".repeat(200),
+			samples: vec![u32::MAX],
+		};
+		let ser = large.clone().serialize_bin();
+		assert_eq!(ser.len(), 10 + 3 + 5 + 10 + 2 + 200 + 1 + 5);
+		assert_eq!(CounterStruct::deserialize_bin(ser).unwrap(), large);
+		// A port that does not fit in a u16
+		assert!(matches!(
+			CounterStruct::deserialize_bin(vec![0, 0x80, 0x80, 0x04]),
+			Err(DeserializationError::NoMatch { field, actual }) if field == "[1]" && actual == "65536"
+		));
+		// A varint that is longer than 64 bits
+		assert!(CounterStruct::deserialize_bin(vec![0xff; 11]).is_err());
+	}
+
+	#[test]
+	fn test_sized_item() {
+		let country = CountryStruct {
+			code: "NZ".into(),
+			name: "New Zealand".into(),
+		};
+		let data = Serde::<EfficientProfile>::try_into_data_profile(country.clone()).unwrap();
+		assert_eq!(data.0.into_serialized_items().count(), 2);
+		assert_eq!(DatumSize::U8.serialize_usize(255).unwrap(), vec![255]);
+		assert_eq!(DatumSize::U16.serialize_usize(256).unwrap(), vec![1, 0]);
+		assert!(matches!(DatumSize::U8.serialize_usize(256), Err(SerializationError::LengthOverflow { length: 256, .. })));
+		#[cfg(feature = "bin")]
+		{
+			let ser = country.clone().serialize_bin();
+			assert_eq!(&ser[..3], &[2, b'N', b'Z']);
+			assert_eq!(ser.len(), 3 + 4 + 11);
+			assert_eq!(CountryStruct::deserialize_bin(ser).unwrap(), country);
+			// Lengths are only written by binary formats, so they are checked as the binary is written
+			let long = CountryStruct {
+				code: "Z".repeat(256),
+				name: "".into(),
+			};
+			match long.clone().try_serialize_bin() {
+				Err(SerializationError::LengthOverflow { field, length, size }) => {
+					assert_eq!(field, "[0]");
+					assert_eq!(length, 256);
+					assert_eq!(size.max_len(), 255);
+				}
+				x => panic!("{:?}", x)
+			}
+			assert!(long.serialize_bin_to(&BinWriter::new(Vec::new())).is_err());
+			// Counts are checked the same way
+			match StrayStruct::default().try_serialize_bin() {
+				Err(SerializationError::LengthOverflow { field, length, .. }) => {
+					assert_eq!(field, "[0]");
+					assert_eq!(length, 256);
+				}
+				x => panic!("{:?}", x)
+			}
+			// Converting without try leaves out the items from the one that does not fit
+			assert!(StrayStruct::default().serialize_bin().is_empty());
+		}
+		// Text formats write the count as it is
+		let items = Serde::<EfficientProfile>::into_data_profile(StrayStruct::default()).items();
+		assert_eq!(items, Some(vec![Datum::U64(256)]));
+	}
+
+	#[test]
+	#[cfg(feature = "bin")]
+	fn test_byte_order() {
+		let sensor = SensorStruct {
+			id: 0x01020304,
+			temperature: 21.5,
+			flags: 0xbeef,
+			offset: -3,
+		};
+		// The layout of the same fields in a #[repr(C, packed)] struct on a little-endian device
+		let mut layout = Vec::new();
+		layout.extend(sensor.id.to_le_bytes());
+		layout.extend(sensor.temperature.to_le_bytes());
+		layout.extend(sensor.flags.to_le_bytes());
+		layout.extend(sensor.offset.to_le_bytes());
+		assert_eq!(sensor.clone().serialize_bin(), layout);
+		assert_eq!(SensorStruct::deserialize_bin(layout).unwrap(), sensor);
+		let mut data = ArrayData::serial_ready();
+		data.set_options(ArrayOptions { byte_order: ByteOrder::LittleEndian, ..Default::default() });
+		data.serialize_item("hi");
+		data.serialize_seq_item(vec![1u16]);
+		let bytes: Vec<u8> = ProfileToData::into(data);
+		assert_eq!(bytes, vec![2, 0, 0, 0, b'h', b'i', 1, 0, 0, 0, 1, 0]);
+	}
+
+	#[test]
+	#[cfg(feature = "bin")]
+	fn test_tagged_bin() {
+		let reading = ReadingStruct {
+			sensor: "hall".into(),
+			value: 0.5,
+			count: 7,
+		};
+		let ser = reading.clone().serialize_bin();
+		assert_eq!(ser.len(), 1 + 4 + 4 + 1 + 8 + 1 + 4);
+		assert_eq!(ReadingStruct::deserialize_bin(ser.clone()).unwrap(), reading);
+		match MisreadStruct::deserialize_bin(ser.clone()) {
+			Err(DeserializationError::InvalidType { field, expected, actual }) => {
+				assert_eq!(field, "[1]");
+				assert_eq!(expected, "u32");
+				assert_eq!(actual, "f64");
+			}
+			x => panic!("{:?}", x)
+		}
+		// Integers of other widths are rejected, even if their value would fit
+		assert!(MisreadStruct::deserialize_bin(vec![0, 0, 0, 0, 1, b'a', 4, 0, 0, 0, 7]).is_ok());
+		match MisreadStruct::deserialize_bin(vec![0, 0, 0, 0, 1, b'a', 2, 7]) {
+			Err(DeserializationError::InvalidType { field, expected, actual }) => {
+				assert_eq!(field, "[1]");
+				assert_eq!(expected, "u32");
+				assert_eq!(actual, "u8");
+			}
+			x => panic!("{:?}", x)
+		}
+		// Tagged data can be read without knowing how it was serialized
+		let datums: Vec<Datum> = std::iter::from_fn({
+			let mut cursor = BinCursor::new(ser);
+			move || cursor.next_tagged()
+		}).collect::<Result<_, _>>().unwrap();
+		assert_eq!(datums, vec![Datum::from("hall"), Datum::from(0.5f64), Datum::from(7u32)]);
+		// Data profiles based on MappedData can be written as binary too
+		let ser = Serde::<ReadableProfile>::serialize::<Vec<u8>>(make_directory());
+		assert_eq!(<DirectoryStruct as Serde<ReadableProfile>>::deserialize(ser.clone()).unwrap(), make_directory());
+		match BinCursor::new(ser).next_tagged() {
+			Some(Ok(Datum::Map(map))) => assert_eq!(map.entries().len(), 3),
+			x => panic!("{:?}", x)
+		}
+		let ser = Serde::<ReadableProfile>::serialize::<Vec<u8>>(make_shelter());
+		assert_eq!(<ShelterStruct as Serde<ReadableProfile>>::deserialize(ser).unwrap(), make_shelter());
+		let ser = Serde::<ReadableProfile>::serialize::<Vec<u8>>(make_canvas());
+		assert_eq!(<CanvasStruct as Serde<ReadableProfile>>::deserialize(ser).unwrap(), make_canvas());
+		// Arrays that each hold one array, ending in an empty array
+		let nested = |depth: usize| {
+			let mut ser = [13, 0, 0, 0, 1].repeat(depth);
+			ser.extend([13, 0, 0, 0, 0]);
+			ser
+		};
+		assert!(BinCursor::new(nested(100)).next_tagged().unwrap().is_ok());
+		match BinCursor::new(nested(1000)).next_tagged() {
+			Some(Err(DeserializationError::NoMatch { actual, .. })) => assert!(actual.contains("nested")),
+			x => panic!("{:?}", x)
+		}
+		assert!(<DirectoryStruct as Serde<ReadableProfile>>::deserialize(nested(1000)).is_err());
+	}
+
+	#[test]
+	fn test_version() {
+		let contact = ContactStruct {
+			name: "Ada".into(),
+			email: "ada@lovelace.org".into(),
+		};
+		let entries = Serde::<ReadableProfile>::into_data_profile(contact.clone()).entries();
+		assert_eq!(entries.get(&Datum::from(VERSION_KEY)), Some(&Datum::U8(1)));
+		assert_eq!(map_roundtrip(contact.clone()), contact);
+		// Serializing by reference records the same version
+		let data = SerializeRef::<ReadableProfile>::to_data_profile(&contact);
+		assert_eq!(data.entries().get(&Datum::from(VERSION_KEY)), Some(&Datum::U8(1)));
+		let map: HashMap<String, Datum> = data.0.into_serialized_entries()
+			.map(|(name, value)| (name.to_key_string(), value))
+			.collect();
+		let deser: ContactStruct = Serde::<ReadableProfile>::from_data_profile(ProfileFromData::try_from(map).unwrap()).unwrap();
+		assert_eq!(deser, contact);
+		// Data without a version was written before the email existed
+		let map = HashMap::from([("name".to_string(), Datum::from("Ada"))]);
+		let deser: ContactStruct = Serde::<ReadableProfile>::from_data_profile(ProfileFromData::try_from(map).unwrap()).unwrap();
+		assert_eq!(deser.email, "ada@example.com");
+		let map = HashMap::from([
+			("name".to_string(), Datum::from("Ada")),
+			(VERSION_KEY.to_string(), Datum::U8(2)),
+		]);
+		match Serde::<ReadableProfile>::from_data_profile(ProfileFromData::try_from(map).unwrap()) {
+			Err::<ContactStruct, _>(DeserializationError::UnsupportedVersion { field, version, latest }) => {
+				assert_eq!(field, VERSION_KEY);
+				assert_eq!(version, 2);
+				assert_eq!(latest, 1);
+			}
+			x => panic!("{:?}", x)
+		}
+		// Array profiles write a header byte before the items of every versioned value
+		let book = AddressBookStruct { contacts: vec![contact.clone()] };
+		let items = book.into_data_profile().items();
+		assert_eq!(items, Some(vec![Datum::U8(0), Datum::U32(1), Datum::U8(1), Datum::from("Ada"), Datum::from("ada@lovelace.org")]));
+		let items = VecDeque::from([Datum::U8(0), Datum::U32(2), Datum::U8(1), Datum::from("Ada"), Datum::from("ada@lovelace.org"), Datum::U8(0), Datum::from("Bob")]);
+		let deser: AddressBookStruct = Serde::<VersionedProfile>::from_data_profile(ProfileFromData::try_from(items).unwrap()).unwrap();
+		assert_eq!(deser.contacts[0], contact);
+		assert_eq!(deser.contacts[1].email, "bob@example.com");
+		let items = VecDeque::from([Datum::U8(0), Datum::U32(1), Datum::U8(3), Datum::from("Ada")]);
+		match Serde::<VersionedProfile>::from_data_profile(ProfileFromData::try_from(items).unwrap()) {
+			Err::<AddressBookStruct, _>(DeserializationError::UnsupportedVersion { field, version, .. }) => {
+				assert_eq!(field, "[0][0]");
+				assert_eq!(version, 3);
+			}
+			x => panic!("{:?}", x)
+		}
+	}
+
+	#[test]
+	fn test_entry_default() {
+		let map = HashMap::from([("port".to_string(), Datum::from(9000u16))]);
+		let deser: ServerStruct = Serde::<ReadableProfile>::from_data_profile(ProfileFromData::try_from(map).unwrap()).unwrap();
+		assert_eq!(deser, ServerStruct { host: "localhost".into(), port: 9000, verbose: false });
+		// Default values are still written unless the profile skips them
+		assert_eq!(Serde::<ReadableProfile>::into_data_profile(deser.clone()).entries().len(), 3);
+		let entries = Serde::<MinimalProfile>::into_data_profile(deser.clone()).entries();
+		assert_eq!(entries, HashMap::from([(Datum::from("port"), Datum::from(9000u16))]));
+		let map = HashMap::from([("port".to_string(), Datum::from("high"))]);
+		match Serde::<MinimalProfile>::from_data_profile(ProfileFromData::try_from(map).unwrap()) {
+			Err::<ServerStruct, _>(DeserializationError::InvalidType { field, .. }) => assert_eq!(field, "port"),
+			x => panic!("{:?}", x)
+		}
+	}
+
+	#[test]
+	fn test_unknown_field() {
+		let map = HashMap::from([
+			("volume".to_string(), Datum::from(7u8)),
+			("agee".to_string(), Datum::from(3u8)),
+			("colour".to_string(), Datum::from("red")),
+		]);
+		match Serde::<StrictProfile>::from_data_profile(ProfileFromData::try_from(map.clone()).unwrap()) {
+			Err::<SettingsStruct, _>(DeserializationError::UnknownField { field, keys }) => {
+				assert_eq!(field, "");
+				assert_eq!(keys, vec!["agee".to_string(), "colour".to_string()]);
+			}
+			x => panic!("{:?}", x)
+		}
+		// Leftover entries are kept when the profile collects them
+		let settings: SettingsStruct = Serde::<ReadableProfile>::from_data_profile(ProfileFromData::try_from(map.clone()).unwrap()).unwrap();
+		assert_eq!(settings.volume, 7);
+		assert_eq!(settings.extra, HashMap::from([("agee".to_string(), Datum::from(3u8)), ("colour".to_string(), Datum::from("red"))]));
+		assert_eq!(map_roundtrip(settings.clone()), settings);
+		// Nested values are checked too
+		let main = HashMap::from([("volume".to_string(), Datum::from(7u8)), ("agee".to_string(), Datum::from(3u8))]);
+		let map = HashMap::from([("main".to_string(), Datum::Map(ProfileFromData::try_from(main).unwrap()))]);
+		match Serde::<StrictProfile>::from_data_profile(ProfileFromData::try_from(map).unwrap()) {
+			Err::<MixerStruct, _>(e @ DeserializationError::UnknownField { .. }) => {
+				assert_eq!(e.to_string(), "field `main`: unknown keys `agee`");
+			}
+			x => panic!("{:?}", x)
+		}
+		// So are the fields of enum variants, and the maps that hold them
+		let map_of = |entries: Vec<(&str, Datum)>| Datum::Map(ProfileFromData::try_from(
+			entries.into_iter().map(|(key, value)| (key.to_string(), value)).collect::<HashMap<_, _>>()
+		).unwrap());
+		let plot = |circle: Vec<(&str, Datum)>, adjacent: Vec<(&str, Datum)>| {
+			let map = HashMap::from([
+				("external".to_string(), map_of(vec![("circle", map_of(circle))])),
+				("adjacent".to_string(), map_of(adjacent)),
+			]);
+			Serde::<StrictProfile>::from_data_profile(ProfileFromData::try_from(map).unwrap())
+		};
+		let rect = || map_of(vec![("width", Datum::from(1u32)), ("height", Datum::from(2u32))]);
+		let deser: PlotStruct = plot(vec![("radius", Datum::from(1.5))], vec![("t", Datum::from("rect")), ("c", rect())]).unwrap();
+		assert_eq!(deser.adjacent, ShapeEnum::Rect { width: 1, height: 2 });
+		match plot(vec![("radius", Datum::from(1.5)), ("colour", Datum::from("red"))], vec![("t", Datum::from("empty"))]) {
+			Err::<PlotStruct, _>(e) => assert_eq!(e.to_string(), "field `external.circle`: unknown keys `colour`"),
+			x => panic!("{:?}", x)
+		}
+		let mut content = vec![("width", Datum::from(1u32)), ("height", Datum::from(2u32)), ("depth", Datum::from(3u32))];
+		match plot(vec![("radius", Datum::from(1.5))], vec![("t", Datum::from("rect")), ("c", map_of(content.split_off(0)))]) {
+			Err::<PlotStruct, _>(e) => assert_eq!(e.to_string(), "field `adjacent.c`: unknown keys `depth`"),
+			x => panic!("{:?}", x)
+		}
+		match plot(vec![("radius", Datum::from(1.5))], vec![("t", Datum::from("rect")), ("c", rect()), ("z", Datum::from(0u8))]) {
+			Err::<PlotStruct, _>(e) => assert_eq!(e.to_string(), "field `adjacent`: unknown keys `z`"),
+			x => panic!("{:?}", x)
+		}
+	}
+
+	#[test]
+	fn test_u64_max() {
+		let make_test = || TestStruct { name: "ferus", age: u64::MAX, id: "gangnam".into() };
+		assert_eq!(map_roundtrip(make_test()).age, u64::MAX);
+		#[cfg(feature = "json")]
+		assert_eq!(TestStruct::deserialize_json(make_test().serialize_json()).unwrap().age, u64::MAX);
+		#[cfg(feature = "bin")]
+		assert_eq!(TestStruct::deserialize_bin(make_test().serialize_bin()).unwrap().age, u64::MAX);
+		// TOML integers are signed, so the value cannot be written without wrapping
+		#[cfg(feature = "toml")]
+		{
+			match make_test().try_serialize_toml() {
+				Err(SerializationError::TOMLError { field, .. }) => assert_eq!(field, "age"),
+				x => panic!("{:?}", x)
+			}
+			// Serializing without try leaves the value out rather than panicking
+			let value: extern_toml::Value = Serde::<ReadableProfile>::serialize(make_test());
+			assert!(value.get("age").is_none());
+			assert_eq!(value["id"].as_str(), Some("gangnam"));
+			let test = TestStruct { age: i64::MAX as u64, ..make_test() };
+			assert_eq!(TestStruct::deserialize_toml(test.serialize_toml()).unwrap().age, i64::MAX as u64);
+		}
+	}
+
+	#[test]
+	fn test_seq() {
+		let _written = roundtrip(make_shelter, |deser| assert_eq!(deser, make_shelter()));
+		#[cfg(feature = "toml")]
+		assert!(_written.toml.contains("[[pets]]"));
+		#[cfg(feature = "json")]
+		assert!(_written.json.contains(r#""ratings":[5,-1,3]"#));
+		#[cfg(feature = "bin")]
+		assert_eq!(_written.bin[..4], [0, 0, 0, 2]);
+		let data = Serde::<EfficientProfile>::into_data_profile(make_shelter());
+		// 3 lengths, 2 tags, 3 ratings and 3 items for each pet
+		assert_eq!(data.0.into_serialized_items().count(), 3 + 2 + 3 + 6);
+	}
+
+	#[test]
+	fn test_map() {
+		let _written = roundtrip(make_directory, |deser| assert_eq!(deser, make_directory()));
+		#[cfg(feature = "toml")]
+		assert!(_written.toml.contains("[pets.tom]"));
+		#[cfg(feature = "json")]
+		assert!(_written.json.contains(r#""443":"https""#));
+		let data = Serde::<EfficientProfile>::into_data_profile(make_directory());
+		// 3 lengths, 2 entries of 2 items each and 1 entry of 4 items
+		assert_eq!(data.0.into_serialized_items().count(), 3 + 4 + 4 + 4);
+	}
+
+	#[test]
+	fn test_enum() {
+		let _written = roundtrip(make_canvas, |deser| assert_eq!(deser, make_canvas()));
+		#[cfg(feature = "json")]
+		{
+			let ser = &_written.json;
+			assert!(ser.contains(r#""external":{"circle":{"radius":1.5}}"#));
+			assert!(ser.contains(r#""unit":"empty""#));
+			assert!(ser.contains(r#""type":"rect""#));
+			assert!(ser.contains(r#""t":"circle""#));
+
+			let ser = r#"{"external": {"rect": {"width": 1, "height": 2}}, "unit": {"circle": {"radius": 3}},
+				"internal": {"type": "empty"}, "adjacent": {"t": "empty"}, "untagged": {}}"#;
+			let deser = CanvasStruct::deserialize_json(ser).unwrap();
+			assert_eq!(deser.external, ShapeEnum::Rect { width: 1, height: 2 });
+			assert_eq!(deser.unit, ShapeEnum::Circle { radius: 3.0 });
+			assert_eq!(deser.untagged, ShapeEnum::Empty);
+			assert!(CanvasStruct::deserialize_json(ser.replace("empty", "square")).is_err());
+		}
+		#[cfg(feature = "bin")]
+		assert_eq!(_written.bin[..9], [0, 63, 248, 0, 0, 0, 0, 0, 0]);
+		let data = Serde::<EfficientProfile>::into_data_profile(make_canvas());
+		// 5 variant indices and 6 fields
+		assert_eq!(data.0.into_serialized_items().count(), 5 + 6);
+	}
+
+	#[test]
+	#[cfg(feature = "derive")]
+	fn test_derive() {
+		#[cfg(feature = "toml")]
+		{
+			let mut src = make_derived();
+			src.cache = 7;
+			let ser = src.serialize_toml();
+			assert!(ser.contains("years = 52"));
+			assert!(!ser.contains("cache"));
+			assert_eq!(DerivedStruct::deserialize_toml(ser).unwrap(), make_derived());
+
+			let deser = DerivedStruct::deserialize_toml("name = \"ferus\"\nyears = 52\ncomment = \"hello\"").unwrap();
+			assert_eq!(deser.nickname, "");
+			assert!(DerivedStruct::deserialize_toml("name = \"ferus\"\nyears = 52").is_err());
+		}
+		#[cfg(feature = "bin")]
+		{
+			let ser = make_derived().serialize_bin();
+			assert_eq!(ser.len(), 4 + 5 + 8 + 4 + 2);
+			let deser = DerivedStruct::deserialize_bin(ser).unwrap();
+			assert_eq!(deser.comment, "");
+		}
+		assert_eq!(map_roundtrip(make_derived()), make_derived());
+		#[cfg(feature = "toml")]
+		{
+			let ser = make_kennel().serialize_toml_ref();
+			assert_eq!(DerivedKennelStruct::deserialize_toml(ser).unwrap(), make_kennel());
+			let mut empty = make_kennel();
+			empty.keeper = None;
+			empty.yard = ShapeEnum::Empty;
+			let ser = empty.serialize_toml_ref();
+			assert!(!ser.contains("keeper"));
+			assert_eq!(DerivedKennelStruct::deserialize_toml(ser).unwrap(), empty);
+
+			let src = "tags = []\nscores = { space = 3 }\npets = []\nmascot = { species = \"cat\", legs = 4, vaccinated = false }\nyard = { circle = { radius = 2.5 } }";
+			let deser = DerivedKennelStruct::deserialize_toml(src).unwrap();
+			assert!(deser.rooms.is_empty());
+			assert_eq!(deser.scores["space"], 3);
+			assert_eq!(deser.yard, ShapeEnum::Circle { radius: 2.5 });
+			assert!(DerivedKennelStruct::deserialize_toml(src.replace("pets = []\n", "")).is_err());
+		}
+		#[cfg(feature = "bin")]
+		{
+			let ser = make_kennel().serialize_bin_ref();
+			assert_eq!(DerivedKennelStruct::deserialize_bin(ser).unwrap(), make_kennel());
+		}
+
+		let data = Serde::<ReadableProfile>::into_data_profile(DerivedTuple("ferus".into(), 3));
+		let keys: Vec<String> = data.0.into_serialized_entries().map(|(name, _)| name.to_key_string()).collect();
+		assert_eq!(keys.len(), 2);
+		assert!(keys.contains(&"0".to_string()) && keys.contains(&"1".to_string()));
+	}
+
+	#[test]
+	#[cfg(feature = "toml")]
+	fn test_serde() {
+		let src = TestStruct {
+			name: "ferus",
+			age: 52,
+			id: "gangnam".into(),
+		};
+		let ser = src.serialize_toml();
+		println!("{}", ser);
+		let deser = TestStruct::deserialize_toml(ser).unwrap();
+		println!("{:?}", deser);
+	}
+
+	#[test]
+	#[cfg(feature = "bin")]
+	fn test_serde2() {
+		let src = TestStruct {
+			name: "ferus",
+			age: 52,
+			id: "gangnam".into(),
+		};
+		let ser = src.serialize_bin();
+		println!("{:?}", ser);
+		let deser = TestStruct::deserialize_bin(ser).unwrap();
+		println!("{:?}", deser);
+	}
+
+	#[test]
+	#[cfg(feature = "json")]
+	fn test_serde3() {
+		let src = TestStruct {
+			name: "ferus",
+			age: 52,
+			id: "gangnam".into(),
+		};
+		let test: TestProfile = convert_data_profile(Serde::<ReadableProfile>::into_data_profile(src));
+		let ser: extern_json::JsonValue = ProfileToData::into(test);
+		println!("{}", ser);
+		let deser: TestStruct = Serde::<ReadableProfile>::from_data_profile(ProfileFromData::try_from(ser).unwrap()).unwrap();
+		println!("{:?}", deser);
+	}
+
+	#[test]
+	#[cfg(feature = "json")]
 	fn test_serde4() {
 		let src = TestStruct {
 			name: "ferus",
@@ -1,9 +1,13 @@
 use std::mem::swap;
 use std::ops::DerefMut;
-pub use array::{DatumArray, ArrayData};
-pub use map::{DatumMap, MappedData};
+use std::str::FromStr;
+pub use array::{DatumArray, ArrayData, ArrayOptions, ArrayProfile, ByteOrder};
+#[cfg(feature = "bin")]
+pub(crate) use array::{ItemSink, SerialItem, count_datum};
+pub use map::{DatumMap, MappedData, MappedOptions, MappedProfile, VERSION_KEY};
 
-use crate::DeserializationError;
+use crate::{DeserializationError, Serde, SerializationError, SerializeRef};
+use crate::datum::{Datum, GetDatumType};
 
 mod map;
 mod array;
@@ -25,6 +29,22 @@ pub trait DataProfile {
 	fn is_serial(&self) -> bool;
 	/// Instantiates a profile that is ready for serialization
 	fn serial_ready() -> Self;
+	/// Returns the version of the data.
+	/// While deserializing, this is the version that the data was written with
+	fn version(&self) -> u8 {
+		0
+	}
+	/// Records the given current version of a value while serializing,
+	/// or reads the version that the data was written with while deserializing.
+	/// Returns an error if the data was written by a version newer than current
+	fn apply_version(&mut self, _current: u8) -> Result<(), DeserializationError> {
+		Ok(())
+	}
+	/// Called once a value has been deserialized from the profile, to check the data that was not read.
+	/// Returns an error if the profile does not allow data to be left over
+	fn finish(&mut self) -> Result<(), DeserializationError> {
+		Ok(())
+	}
 }
 
 
@@ -40,16 +60,418 @@ pub trait ProfileFromData<D>: Sized {
 /// The data profile must be ready for serialization
 pub trait ProfileToData<D> {
 	/// Converts the data profile into another type.
-	/// Implementors must ensure that this conversion will not fail
+	/// Implementors must ensure that this conversion will not fail.
+	/// Formats that cannot express every datum leave out what they cannot express, which try_into_data reports instead
 	fn into(self) -> D;
+	/// Converts the data profile into another type, failing if the data cannot be expressed in that type.
+	/// Formats that can express every datum do not need to implement this
+	fn try_into_data(self) -> Result<D, SerializationError> where Self: Sized {
+		Ok(self.into())
+	}
+}
+
+
+/// Serializes and deserializes the fields of types that derive Serde, regardless of the base of the profile.
+/// Profiles based on MappedData use named entries, while profiles based on ArrayData use items.
+/// This is implemented on the base, so fields that need the profile itself, such as nested values, take it as data.
+///
+/// This is used by the derive macro, and you will not need to call it directly
+#[doc(hidden)]
+pub trait DeriveProfile: Sized {
+	/// Either serializes or deserializes a field
+	fn derive_field<V, E>(&mut self, name: &'static str, value: &mut V) -> Result<(), DeserializationError>
+		where
+			V: Into<Datum> + TryFrom<Datum, Error=E> + Default + GetDatumType,
+			DeserializationError: From<E>;
+	/// Either serializes or deserializes a field, leaving it as its default value if it is missing
+	fn derive_field_or_default<V, E>(&mut self, name: &'static str, value: &mut V) -> Result<(), DeserializationError>
+		where
+			V: Into<Datum> + TryFrom<Datum, Error=E> + Default + GetDatumType + PartialEq,
+			DeserializationError: From<E>;
+	/// Serializes a field, leaving it intact
+	fn derive_field_ref<'a, V>(&mut self, name: &'static str, value: &'a V) where &'a V: Into<Datum>;
+	/// Either serializes or deserializes an optional field
+	fn derive_optional_field<V, E>(&mut self, name: &'static str, value: &mut Option<V>) -> Result<(), DeserializationError>
+		where
+			V: Into<Datum> + TryFrom<Datum, Error=E> + GetDatumType,
+			DeserializationError: From<E>;
+	/// Serializes an optional field, leaving it intact
+	fn derive_optional_field_ref<'a, V>(&mut self, name: &'static str, value: &'a Option<V>) where &'a V: Into<Datum>;
+	/// Either serializes or deserializes a sequence field
+	fn derive_seq_field<V, E>(&mut self, name: &'static str, value: &mut Vec<V>) -> Result<(), DeserializationError>
+		where
+			V: Into<Datum> + TryFrom<Datum, Error=E> + GetDatumType,
+			DeserializationError: From<E>;
+	/// Serializes a sequence field, leaving it intact
+	fn derive_seq_field_ref<'a, V>(&mut self, name: &'static str, value: &'a [V]) where &'a V: Into<Datum>;
+	/// Either serializes or deserializes a map field, such as a HashMap or BTreeMap
+	fn derive_map_field<K, V, M, KE, VE>(&mut self, name: &'static str, value: &mut M) -> Result<(), DeserializationError>
+		where
+			K: Into<Datum> + TryFrom<Datum, Error=KE> + FromStr + GetDatumType,
+			V: Into<Datum> + TryFrom<Datum, Error=VE> + GetDatumType,
+			M: IntoIterator<Item=(K, V)> + Extend<(K, V)> + Default,
+			M::IntoIter: ExactSizeIterator,
+			DeserializationError: From<KE> + From<VE>;
+	/// Serializes a map field, leaving it intact
+	fn derive_map_field_ref<'a, K, V, M>(&mut self, name: &'static str, value: M)
+		where
+			K: 'a,
+			&'a K: Into<Datum>,
+			V: 'a,
+			&'a V: Into<Datum>,
+			M: IntoIterator<Item=(&'a K, &'a V)>,
+			M::IntoIter: ExactSizeIterator;
+	/// Either serializes or deserializes a field that implements Serde with the profile
+	fn derive_nested_field<P, T>(data: &mut P, name: &'static str, value: &mut T) -> Result<(), DeserializationError>
+		where
+			P: DataProfile + DerefMut<Target=Self>,
+			T: Serde<P> + Default;
+	/// Serializes a field that implements SerializeRef with the profile, leaving it intact
+	fn derive_nested_field_ref<P, T>(data: &mut P, name: &'static str, value: &T)
+		where
+			P: DataProfile + DerefMut<Target=Self>,
+			T: SerializeRef<P>;
+	/// Either serializes or deserializes a sequence field whose items implement Serde with the profile
+	fn derive_nested_seq_field<P, T>(data: &mut P, name: &'static str, value: &mut Vec<T>) -> Result<(), DeserializationError>
+		where
+			P: DataProfile + DerefMut<Target=Self>,
+			T: Serde<P> + Default;
+	/// Serializes a sequence field whose items implement SerializeRef with the profile, leaving it intact
+	fn derive_nested_seq_field_ref<P, T>(data: &mut P, name: &'static str, value: &[T])
+		where
+			P: DataProfile + DerefMut<Target=Self>,
+			T: SerializeRef<P>;
+	/// Either serializes or deserializes a map field whose values implement Serde with the profile
+	fn derive_nested_map_field<P, K, T, M, KE>(data: &mut P, name: &'static str, value: &mut M) -> Result<(), DeserializationError>
+		where
+			P: DataProfile + DerefMut<Target=Self>,
+			K: Into<Datum> + TryFrom<Datum, Error=KE> + FromStr + GetDatumType,
+			T: Serde<P> + Default,
+			M: IntoIterator<Item=(K, T)> + Extend<(K, T)> + Default,
+			M::IntoIter: ExactSizeIterator,
+			DeserializationError: From<KE>;
+	/// Serializes a map field whose values implement SerializeRef with the profile, leaving it intact
+	fn derive_nested_map_field_ref<'a, P, K, T, M>(data: &mut P, name: &'static str, value: M)
+		where
+			P: DataProfile + DerefMut<Target=Self>,
+			K: 'a,
+			&'a K: Into<Datum>,
+			T: SerializeRef<P> + 'a,
+			M: IntoIterator<Item=(&'a K, &'a T)>,
+			M::IntoIter: ExactSizeIterator;
+	/// Either serializes or deserializes an enum field whose variants carry data.
+	/// Profiles based on MappedData use external tagging
+	fn derive_enum_field<P, T>(data: &mut P, name: &'static str, value: &mut T) -> Result<(), DeserializationError>
+		where
+			P: DataProfile + DerefMut<Target=Self>,
+			T: Serde<P> + EnumVariants + Default;
+	/// Serializes an enum field whose variants carry data, leaving it intact
+	fn derive_enum_field_ref<P, T>(data: &mut P, name: &'static str, value: &T)
+		where
+			P: DataProfile + DerefMut<Target=Self>,
+			T: SerializeRef<P> + EnumVariants;
+}
+
+
+impl DeriveProfile for MappedData {
+	fn derive_field<V, E>(&mut self, name: &'static str, value: &mut V) -> Result<(), DeserializationError>
+		where
+			V: Into<Datum> + TryFrom<Datum, Error=E> + Default + GetDatumType,
+			DeserializationError: From<E>
+	{
+		self.serde_entry(name, value)
+	}
+	fn derive_field_or_default<V, E>(&mut self, name: &'static str, value: &mut V) -> Result<(), DeserializationError>
+		where
+			V: Into<Datum> + TryFrom<Datum, Error=E> + Default + GetDatumType + PartialEq,
+			DeserializationError: From<E>
+	{
+		self.serde_entry_or_default(name, value)
+	}
+	fn derive_field_ref<'a, V>(&mut self, name: &'static str, value: &'a V) where &'a V: Into<Datum> {
+		self.serialize_entry(name, value);
+	}
+	fn derive_optional_field<V, E>(&mut self, name: &'static str, value: &mut Option<V>) -> Result<(), DeserializationError>
+		where
+			V: Into<Datum> + TryFrom<Datum, Error=E> + GetDatumType,
+			DeserializationError: From<E>
+	{
+		self.serde_optional_entry(name, value)
+	}
+	fn derive_optional_field_ref<'a, V>(&mut self, name: &'static str, value: &'a Option<V>) where &'a V: Into<Datum> {
+		self.serialize_optional_entry(name, value.as_ref());
+	}
+	fn derive_seq_field<V, E>(&mut self, name: &'static str, value: &mut Vec<V>) -> Result<(), DeserializationError>
+		where
+			V: Into<Datum> + TryFrom<Datum, Error=E> + GetDatumType,
+			DeserializationError: From<E>
+	{
+		self.serde_seq_entry(name, value)
+	}
+	fn derive_seq_field_ref<'a, V>(&mut self, name: &'static str, value: &'a [V]) where &'a V: Into<Datum> {
+		self.serialize_seq_entry(name, value.iter().collect());
+	}
+	fn derive_map_field<K, V, M, KE, VE>(&mut self, name: &'static str, value: &mut M) -> Result<(), DeserializationError>
+		where
+			K: Into<Datum> + TryFrom<Datum, Error=KE> + FromStr + GetDatumType,
+			V: Into<Datum> + TryFrom<Datum, Error=VE> + GetDatumType,
+			M: IntoIterator<Item=(K, V)> + Extend<(K, V)> + Default,
+			M::IntoIter: ExactSizeIterator,
+			DeserializationError: From<KE> + From<VE>
+	{
+		self.serde_map_entry(name, value)
+	}
+	fn derive_map_field_ref<'a, K, V, M>(&mut self, name: &'static str, value: M)
+		where
+			K: 'a,
+			&'a K: Into<Datum>,
+			V: 'a,
+			&'a V: Into<Datum>,
+			M: IntoIterator<Item=(&'a K, &'a V)>,
+			M::IntoIter: ExactSizeIterator
+	{
+		self.serialize_map_entry(name, value);
+	}
+	fn derive_nested_field<P, T>(data: &mut P, name: &'static str, value: &mut T) -> Result<(), DeserializationError>
+		where
+			P: DataProfile + DerefMut<Target=Self>,
+			T: Serde<P> + Default
+	{
+		data.serde_nested_entry(name, value)
+	}
+	fn derive_nested_field_ref<P, T>(data: &mut P, name: &'static str, value: &T)
+		where
+			P: DataProfile + DerefMut<Target=Self>,
+			T: SerializeRef<P>
+	{
+		data.serialize_nested_entry_ref(name, value);
+	}
+	fn derive_nested_seq_field<P, T>(data: &mut P, name: &'static str, value: &mut Vec<T>) -> Result<(), DeserializationError>
+		where
+			P: DataProfile + DerefMut<Target=Self>,
+			T: Serde<P> + Default
+	{
+		data.serde_nested_seq_entry(name, value)
+	}
+	fn derive_nested_seq_field_ref<P, T>(data: &mut P, name: &'static str, value: &[T])
+		where
+			P: DataProfile + DerefMut<Target=Self>,
+			T: SerializeRef<P>
+	{
+		data.serialize_nested_seq_entry_ref(name, value);
+	}
+	fn derive_nested_map_field<P, K, T, M, KE>(data: &mut P, name: &'static str, value: &mut M) -> Result<(), DeserializationError>
+		where
+			P: DataProfile + DerefMut<Target=Self>,
+			K: Into<Datum> + TryFrom<Datum, Error=KE> + FromStr + GetDatumType,
+			T: Serde<P> + Default,
+			M: IntoIterator<Item=(K, T)> + Extend<(K, T)> + Default,
+			M::IntoIter: ExactSizeIterator,
+			DeserializationError: From<KE>
+	{
+		data.serde_nested_map_entry(name, value)
+	}
+	fn derive_nested_map_field_ref<'a, P, K, T, M>(data: &mut P, name: &'static str, value: M)
+		where
+			P: DataProfile + DerefMut<Target=Self>,
+			K: 'a,
+			&'a K: Into<Datum>,
+			T: SerializeRef<P> + 'a,
+			M: IntoIterator<Item=(&'a K, &'a T)>,
+			M::IntoIter: ExactSizeIterator
+	{
+		data.serialize_nested_map_entry_ref(name, value);
+	}
+	fn derive_enum_field<P, T>(data: &mut P, name: &'static str, value: &mut T) -> Result<(), DeserializationError>
+		where
+			P: DataProfile + DerefMut<Target=Self>,
+			T: Serde<P> + EnumVariants + Default
+	{
+		data.serde_enum_entry(name, value, EnumTagging::External)
+	}
+	fn derive_enum_field_ref<P, T>(data: &mut P, name: &'static str, value: &T)
+		where
+			P: DataProfile + DerefMut<Target=Self>,
+			T: SerializeRef<P> + EnumVariants
+	{
+		data.serialize_enum_entry_ref(name, value, EnumTagging::External);
+	}
+}
+
+
+// Items are only identified by their position, so names are ignored, and items are never missing
+impl DeriveProfile for ArrayData {
+	fn derive_field<V, E>(&mut self, _name: &'static str, value: &mut V) -> Result<(), DeserializationError>
+		where
+			V: Into<Datum> + TryFrom<Datum, Error=E> + Default + GetDatumType,
+			DeserializationError: From<E>
+	{
+		self.serde_item(value)
+	}
+	fn derive_field_or_default<V, E>(&mut self, name: &'static str, value: &mut V) -> Result<(), DeserializationError>
+		where
+			V: Into<Datum> + TryFrom<Datum, Error=E> + Default + GetDatumType + PartialEq,
+			DeserializationError: From<E>
+	{
+		self.derive_field(name, value)
+	}
+	fn derive_field_ref<'a, V>(&mut self, _name: &'static str, value: &'a V) where &'a V: Into<Datum> {
+		self.serialize_item(value);
+	}
+	fn derive_optional_field<V, E>(&mut self, _name: &'static str, value: &mut Option<V>) -> Result<(), DeserializationError>
+		where
+			V: Into<Datum> + TryFrom<Datum, Error=E> + GetDatumType,
+			DeserializationError: From<E>
+	{
+		self.serde_optional_item(value)
+	}
+	fn derive_optional_field_ref<'a, V>(&mut self, _name: &'static str, value: &'a Option<V>) where &'a V: Into<Datum> {
+		self.serialize_optional_item(value.as_ref());
+	}
+	fn derive_seq_field<V, E>(&mut self, _name: &'static str, value: &mut Vec<V>) -> Result<(), DeserializationError>
+		where
+			V: Into<Datum> + TryFrom<Datum, Error=E> + GetDatumType,
+			DeserializationError: From<E>
+	{
+		self.serde_seq_item(value)
+	}
+	fn derive_seq_field_ref<'a, V>(&mut self, _name: &'static str, value: &'a [V]) where &'a V: Into<Datum> {
+		self.serialize_seq_item(value.iter().collect());
+	}
+	fn derive_map_field<K, V, M, KE, VE>(&mut self, _name: &'static str, value: &mut M) -> Result<(), DeserializationError>
+		where
+			K: Into<Datum> + TryFrom<Datum, Error=KE> + FromStr + GetDatumType,
+			V: Into<Datum> + TryFrom<Datum, Error=VE> + GetDatumType,
+			M: IntoIterator<Item=(K, V)> + Extend<(K, V)> + Default,
+			M::IntoIter: ExactSizeIterator,
+			DeserializationError: From<KE> + From<VE>
+	{
+		self.serde_map_item(value)
+	}
+	fn derive_map_field_ref<'a, K, V, M>(&mut self, _name: &'static str, value: M)
+		where
+			K: 'a,
+			&'a K: Into<Datum>,
+			V: 'a,
+			&'a V: Into<Datum>,
+			M: IntoIterator<Item=(&'a K, &'a V)>,
+			M::IntoIter: ExactSizeIterator
+	{
+		self.serialize_map_item(value);
+	}
+	fn derive_nested_field<P, T>(data: &mut P, _name: &'static str, value: &mut T) -> Result<(), DeserializationError>
+		where
+			P: DataProfile + DerefMut<Target=Self>,
+			T: Serde<P> + Default
+	{
+		data.serde_nested_item(value)
+	}
+	fn derive_nested_field_ref<P, T>(data: &mut P, _name: &'static str, value: &T)
+		where
+			P: DataProfile + DerefMut<Target=Self>,
+			T: SerializeRef<P>
+	{
+		data.serialize_nested_item_ref(value);
+	}
+	fn derive_nested_seq_field<P, T>(data: &mut P, _name: &'static str, value: &mut Vec<T>) -> Result<(), DeserializationError>
+		where
+			P: DataProfile + DerefMut<Target=Self>,
+			T: Serde<P> + Default
+	{
+		data.serde_nested_seq_item(value)
+	}
+	fn derive_nested_seq_field_ref<P, T>(data: &mut P, _name: &'static str, value: &[T])
+		where
+			P: DataProfile + DerefMut<Target=Self>,
+			T: SerializeRef<P>
+	{
+		data.serialize_nested_seq_item_ref(value);
+	}
+	fn derive_nested_map_field<P, K, T, M, KE>(data: &mut P, _name: &'static str, value: &mut M) -> Result<(), DeserializationError>
+		where
+			P: DataProfile + DerefMut<Target=Self>,
+			K: Into<Datum> + TryFrom<Datum, Error=KE> + FromStr + GetDatumType,
+			T: Serde<P> + Default,
+			M: IntoIterator<Item=(K, T)> + Extend<(K, T)> + Default,
+			M::IntoIter: ExactSizeIterator,
+			DeserializationError: From<KE>
+	{
+		data.serde_nested_map_item(value)
+	}
+	fn derive_nested_map_field_ref<'a, P, K, T, M>(data: &mut P, _name: &'static str, value: M)
+		where
+			P: DataProfile + DerefMut<Target=Self>,
+			K: 'a,
+			&'a K: Into<Datum>,
+			T: SerializeRef<P> + 'a,
+			M: IntoIterator<Item=(&'a K, &'a T)>,
+			M::IntoIter: ExactSizeIterator
+	{
+		data.serialize_nested_map_item_ref(value);
+	}
+	fn derive_enum_field<P, T>(data: &mut P, _name: &'static str, value: &mut T) -> Result<(), DeserializationError>
+		where
+			P: DataProfile + DerefMut<Target=Self>,
+			T: Serde<P> + EnumVariants + Default
+	{
+		data.serde_enum_item(value)
+	}
+	fn derive_enum_field_ref<P, T>(data: &mut P, _name: &'static str, value: &T)
+		where
+			P: DataProfile + DerefMut<Target=Self>,
+			T: SerializeRef<P> + EnumVariants
+	{
+		data.serialize_enum_item_ref(value);
+	}
+}
+
+
+/// Identifies the variants of an enum whose variants carry data.
+///
+/// The fields of the current variant are handled by the Serde implementation of the enum,
+/// while this trait is used to write and read which variant is current
+pub trait EnumVariants: Sized {
+	/// The names of all variants, in declaration order
+	const VARIANTS: &'static [&'static str];
+	/// Returns the index of the current variant in VARIANTS
+	fn variant_index(&self) -> usize;
+	/// Creates the variant at the given index in VARIANTS, with default fields.
+	/// Returns None if the index is out of range
+	fn from_variant_index(index: usize) -> Option<Self>;
+}
+
+
+/// How the variant of an enum is identified in data profiles based on MappedData.
+/// Profiles based on ArrayData always prefix the fields with the index of the variant
+#[derive(Debug, Copy, Clone)]
+pub enum EnumTagging {
+	/// The variant is written as a map with a single entry, named after the variant, holding the fields.
+	/// Variants without fields are written as just their name
+	External,
+	/// The name of the variant is written to an entry with the given name, alongside the fields
+	Internal(&'static str),
+	/// The name of the variant is written to the tag entry, and the fields are written to the content entry.
+	/// The content entry is omitted for variants without fields
+	Adjacent {
+		/// The name of the entry holding the name of the variant
+		tag: &'static str,
+		/// The name of the entry holding the fields of the variant
+		content: &'static str,
+	},
+	/// Only the fields are written.
+	/// When deserializing, the first variant whose fields deserialize successfully is used
+	Untagged,
 }
 
 
 /// Creates a new data profile, from an existing profile, that can be applied onto your types.
 /// All required functionality is automatically implemented
+///
+/// Options for the base profile can be given in braces after the base,
+/// such as `CompactProfile use ArrayData { pack_bools: true }`
 #[macro_export]
 macro_rules! make_data_profile {
-    ($(#[$attr:meta])* $name: ident use $base: ty) => {
+    ($(#[$attr:meta])* $name: ident use $base: ty $({ $($option: ident : $value: expr),* $(,)? })?) => {
 
 // Define new type
 $(#[$attr])*
@@ -76,14 +498,37 @@ impl DataProfile for $name {
 		self.deref().is_serial()
 	}
 	fn serial_ready() -> Self {
-		Self(<$base>::serial_ready())
+		#[allow(unused_mut)]
+		let mut base = <$base>::serial_ready();
+		$(
+		let mut options = base.options();
+		$(options.$option = $value;)*
+		base.set_options(options);
+		)?
+		Self(base)
+	}
+	fn version(&self) -> u8 {
+		self.deref().version()
+	}
+	fn apply_version(&mut self, current: u8) -> Result<(), DeserializationError> {
+		self.deref_mut().apply_version(current)
+	}
+	fn finish(&mut self) -> Result<(), DeserializationError> {
+		self.deref_mut().finish()
 	}
 }
 
 // Inherit all ProfileFromData traits from base
 impl<D> ProfileFromData<D> for $name where $base: ProfileFromData<D> {
 	fn try_from(data: D) -> Result<Self, DeserializationError> {
-		Ok(Self(ProfileFromData::try_from(data)?))
+		#[allow(unused_mut)]
+		let mut base: $base = ProfileFromData::try_from(data)?;
+		$(
+		let mut options = base.options();
+		$(options.$option = $value;)*
+		base.set_options(options);
+		)?
+		Ok(Self(base))
 	}
 }
 
@@ -92,6 +537,9 @@ impl<D> ProfileToData<D> for $name where $base: ProfileToData<D> {
 	fn into(self) -> D {
 		ProfileToData::into(self.0)
 	}
+	fn try_into_data(self) -> Result<D, $crate::SerializationError> {
+		ProfileToData::try_into_data(self.0)
+	}
 }
     
     };
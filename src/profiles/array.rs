@@ -1,47 +1,185 @@
 use std::fmt::{Debug, Write};
-use std::mem::{take};
+use std::iter::once;
+use std::mem::{swap, take};
+use std::ops::DerefMut;
+use std::rc::Rc;
 use std::vec::IntoIter;
 
-use crate::{DataProfile, DeserializationError, ProfileFromData};
+use crate::{DataProfile, DeserializationError, DeserializeNew, EnumVariants, ProfileFromData, Serde, SerializationError, SerializeRef, TransformResult, deserialize_new_value, serde_value};
 use crate::datum::{Datum, DatumSize, DatumType, GetDatumType};
 use super::SerdeData;
 
-type SerdeArray = SerdeData<Vec<(Datum, DatumSize)>, dyn DatumArray>;
+type SerdeArray = SerdeData<Vec<SerialItem>, dyn DatumArray>;
+
+
+/// An item that has been serialized
+#[derive(Debug, Clone)]
+pub(crate) enum SerialItem {
+	/// A datum, along with the size of its length prefix
+	Datum(Datum, DatumSize),
+	/// The length of a sequence or map, or the index of an enum variant, which is written as an integer of the given size.
+	/// Binary formats check that it fits in the size when writing it, the same way they check length prefixes
+	Count(usize, DatumSize),
+}
+
+
+impl SerialItem {
+	/// Returns the datum of the item, along with the size of its length prefix.
+	/// Counts that do not fit in their size are given as a u64
+	fn into_datum(self) -> (Datum, DatumSize) {
+		match self {
+			Self::Datum(datum, size) => (datum, size),
+			Self::Count(len, size) => (count_datum(len, size).unwrap_or(Datum::U64(len as u64)), size)
+		}
+	}
+}
+
+
+/// Converts a count into an integer of the given size.
+/// Returns an error if it does not fit
+pub(crate) fn count_datum(len: usize, size: DatumSize) -> Result<Datum, SerializationError> {
+	let datum = match size {
+		DatumSize::U8 => u8::try_from(len).map(Datum::U8).ok(),
+		DatumSize::U16 => u16::try_from(len).map(Datum::U16).ok(),
+		DatumSize::U32 => u32::try_from(len).map(Datum::U32).ok(),
+		DatumSize::U64 => u64::try_from(len).map(Datum::U64).ok()
+	};
+	datum.ok_or(SerializationError::LengthOverflow { field: "".into(), length: len, size })
+}
+
+
+/// Receives the items of a data profile as soon as they are complete, instead of the profile keeping them.
+/// Binary writers stream data this way
+pub(crate) trait ItemSink: Debug {
+	/// Receives the next item, which is laid out according to the given options
+	fn push(&self, item: SerialItem, options: ArrayOptions);
+}
 
 
 impl SerdeArray {
+	/// Pushes a serialized item. Does nothing while deserializing
+	fn push(&mut self, item: SerialItem) {
+		if let Self::Serializing(x) = self {
+			x.push(item);
+		}
+	}
 	fn push_item<T: Into<Datum>>(&mut self, item: T) {
 		self.push_item_sized(item, DatumSize::U32)
 	}
+	/// Pushes an item. Does nothing while deserializing
 	fn push_item_sized<T: Into<Datum>>(&mut self, item: T, size: DatumSize) {
-		match self {
-			Self::Deserializing(_) => panic!("Attempting to push item while deserializing!"),
-			Self::Serializing(x) => x.push((item.into(), size))
-		}
+		self.push(SerialItem::Datum(item.into(), size));
 	}
 	fn get_item<T: GetDatumType>(&mut self) -> Result<Datum, DeserializationError> {
 		self.get_item_sized::<T>(DatumSize::U32)
 	}
+	/// Gets an item. There are no items to get while serializing
 	fn get_item_sized<T: GetDatumType>(&mut self, size: DatumSize) -> Result<Datum, DeserializationError> {
 		match self {
-			Self::Serializing(_) => panic!("Attempting to get item while serializing!"),
-			Self::Deserializing(x) => x.get_datum(T::get_datum_type(), size).map_err(Into::into)
+			Self::Serializing(_) => Err(DeserializationError::UnexpectedEOF),
+			Self::Deserializing(x) => x.get_datum(T::get_datum_type(), size)
 		}
 	}
 }
 
 
+/// Takes every remaining item out of the given source.
+/// Only sources that know how many items they have left can be taken from,
+/// as the types of the items are not known
+fn take_items(source: &mut dyn DatumArray) -> Vec<Datum> {
+	let remaining = source.remaining().unwrap_or(0);
+	(0..remaining)
+		.map_while(|_| source.get_datum(DatumType::String, DatumSize::U32).ok())
+		.collect()
+}
+
+
 pub trait DatumArray: Debug {
 	fn get_datum(&mut self, datum_type: DatumType, datum_size: DatumSize) -> Result<Datum, DeserializationError>;
+	/// Applies the options of the data profile that is reading from this source.
+	/// Sources that lay out items according to the options, such as binary sources, should store them
+	fn set_options(&mut self, _options: ArrayOptions) {}
+	/// Returns the number of items left, if it is known.
+	/// Sources that hold standalone arrays, such as TOML and JSON arrays, should know this
+	fn remaining(&self) -> Option<usize> {
+		None
+	}
+	/// Clones the items that have not been taken yet into a new source
+	fn boxed_clone(&self) -> Box<dyn DatumArray>;
+}
+
+
+impl Clone for SerdeArray {
+	fn clone(&self) -> Self {
+		match self {
+			Self::Deserializing(x) => Self::Deserializing(x.boxed_clone()),
+			Self::Serializing(x) => Self::Serializing(x.clone())
+		}
+	}
+}
+
+
+/// Options that change how the items of an ArrayData are laid out.
+/// These can be set for each data profile through the make_data_profile macro
+#[derive(Debug, Copy, Clone, Default)]
+pub struct ArrayOptions {
+	/// Packs consecutive booleans into single bytes, 8 booleans to a byte
+	pub pack_bools: bool,
+	/// Writes integers wider than a byte, and length prefixes, as LEB128 varints, so that small numbers take fewer bytes.
+	/// Signed integers are zigzag encoded first. The sizes given to sized items are ignored
+	pub varint: bool,
+	/// The order of the bytes of numbers and length prefixes
+	pub byte_order: ByteOrder,
+	/// Writes a one byte type tag before every item, so that the data describes its own types.
+	/// Items of the wrong type are then caught when deserializing, instead of being misread.
+	/// Integers must be read with the same width they were written with
+	pub tagged: bool,
+	/// Writes a header byte with the version of every value before its items,
+	/// so that data written by older versions can be read and migrated.
+	/// Without it, data is always read as the current version
+	pub versioned: bool,
+}
+
+
+/// The order in which the bytes of numbers are written
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub enum ByteOrder {
+	/// The most significant byte comes first
+	#[default]
+	BigEndian,
+	/// The least significant byte comes first, as on x86 and most ARM devices
+	LittleEndian,
+}
+
+
+impl ByteOrder {
+	/// The byte order of the target platform, which matches the layout of #[repr(C)] structs
+	pub const NATIVE: ByteOrder = if cfg!(target_endian = "little") {
+		ByteOrder::LittleEndian
+	} else {
+		ByteOrder::BigEndian
+	};
 }
 
 
 /// A base data profile for data that is stored in an array.
 /// You will not instantiate this directly, but you will make aliases of this using the make_data_profile macro
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct ArrayData {
 	serializing: bool,
 	data: SerdeArray,
+	options: ArrayOptions,
+	/// The number of bits used in the current byte of packed booleans.
+	/// 0 if the last item was not a packed boolean
+	packed_bits: u8,
+	/// The byte of packed booleans currently being read
+	packed_byte: u8,
+	/// The number of items deserialized so far, used to identify items in errors
+	index: usize,
+	version: u8,
+	/// Receives the items as soon as they are complete while serializing, if set.
+	/// Clones share the sink
+	sink: Option<Rc<dyn ItemSink>>,
 }
 
 
@@ -53,23 +191,209 @@ impl DataProfile for ArrayData {
 		Self {
 			serializing: true,
 			data: SerdeArray::Serializing(Vec::new()),
+			options: ArrayOptions::default(),
+			packed_bits: 0,
+			packed_byte: 0,
+			index: 0,
+			version: 0,
+			sink: None,
+		}
+	}
+	fn version(&self) -> u8 {
+		self.version
+	}
+	fn apply_version(&mut self, current: u8) -> Result<(), DeserializationError> {
+		self.version = current;
+		if !self.options.versioned {
+			return Ok(())
 		}
+		if self.serializing {
+			self.push_item(current.into());
+			self.flush();
+			return Ok(())
+		}
+		self.version = self.read()?;
+		if self.version > current {
+			return Err(DeserializationError::UnsupportedVersion { field: String::new(), version: self.version, latest: current })
+		}
+		Ok(())
 	}
 }
 
 
 impl ArrayData {
-	/// Consumes self and returns an iterator over data and their sizes
+	/// Pushes an item, packing it with the previous booleans if enabled
+	fn push_item(&mut self, item: Datum) {
+		self.push_item_sized(item, DatumSize::U32)
+	}
+	/// Pushes an item whose length is written as an integer of the given size,
+	/// packing it with the previous booleans if enabled
+	fn push_item_sized(&mut self, item: Datum, size: DatumSize) {
+		let b = match item {
+			Datum::Bool(b) if self.options.pack_bools => b,
+			item => {
+				self.packed_bits = 0;
+				self.data.push_item_sized(item, size);
+				return self.flush()
+			}
+		};
+		if self.packed_bits == 0 || self.packed_bits == 8 {
+			self.data.push_item(0u8);
+			self.packed_bits = 0;
+		}
+		if let SerdeArray::Serializing(items) = &mut self.data {
+			if let Some(SerialItem::Datum(Datum::U8(byte), _)) = items.last_mut() {
+				*byte |= (b as u8) << self.packed_bits;
+			}
+		}
+		self.packed_bits += 1;
+		self.flush();
+	}
+	/// Pushes the items of a nested profile, as if they were items of self
+	fn push_nested(&mut self, nested: ArrayData) {
+		for item in nested.into_items() {
+			self.data.push(item);
+		}
+		self.packed_bits = 0;
+		self.flush();
+	}
+	/// Pushes the length of a sequence or map, or the index of an enum variant, as an integer of the given size.
+	/// Binary formats return an error if it does not fit
+	fn push_count(&mut self, len: usize, size: DatumSize) {
+		self.packed_bits = 0;
+		self.data.push(SerialItem::Count(len, size));
+		self.flush();
+	}
+	/// Hands the items that are complete to the sink, if there is one.
+	/// A byte of packed booleans is held back until it is full, or until an item that is not a boolean follows it
+	fn flush(&mut self) {
+		if let (Some(sink), SerdeArray::Serializing(items)) = (&self.sink, &mut self.data) {
+			let held = if self.packed_bits.is_multiple_of(8) { 0 } else { 1 };
+			let complete = items.len().saturating_sub(held);
+			for item in items.drain(..complete) {
+				sink.push(item, self.options);
+			}
+		}
+	}
+	/// Hands every item to the given sink as soon as it is complete, rather than keeping it.
+	/// Items that were serialized before the sink was set are handed over first
+	#[cfg(feature = "bin")]
+	pub(crate) fn set_sink(&mut self, sink: Rc<dyn ItemSink>) {
+		self.sink = Some(sink);
+		self.flush();
+	}
+	/// Hands the items that are left, including a byte of packed booleans that is not full, to the sink, and removes the sink
+	#[cfg(feature = "bin")]
+	pub(crate) fn close_sink(&mut self) {
+		self.packed_bits = 0;
+		self.flush();
+		self.sink = None;
+	}
+	/// Gets the length of a sequence, stored as an integer of the given size
+	fn get_len(&mut self, size: DatumSize) -> Result<usize, DeserializationError> {
+		usize::try_from(match size {
+			DatumSize::U8 => self.get_item::<u8>()?,
+			DatumSize::U16 => self.get_item::<u16>()?,
+			DatumSize::U32 => self.get_item::<u32>()?,
+			DatumSize::U64 => self.get_item::<u64>()?
+		})
+	}
+	/// Returns the path segment that identifies the next item in errors, and moves on to the item after it.
+	/// Every deserialize method counts as one item, no matter how many items it reads from the source
+	fn next_path(&mut self) -> String {
+		let path = format!("[{}]", self.index);
+		self.index += 1;
+		path
+	}
+	/// Gets an item and converts it into the given type
+	fn read<T, E>(&mut self) -> Result<T, DeserializationError>
+		where
+			T: TryFrom<Datum, Error=E> + GetDatumType,
+			DeserializationError: From<E>
+	{
+		self.read_sized(DatumSize::U32)
+	}
+	/// Gets an item whose length is written as an integer of the given size, and converts it into the given type
+	fn read_sized<T, E>(&mut self, size: DatumSize) -> Result<T, DeserializationError>
+		where
+			T: TryFrom<Datum, Error=E> + GetDatumType,
+			DeserializationError: From<E>
+	{
+		Ok(T::try_from(self.get_item_sized::<T>(size)?)?)
+	}
+	/// Returns the number of items left to deserialize, if it is known
+	pub(crate) fn remaining(&self) -> Option<usize> {
+		match &self.data {
+			SerdeArray::Deserializing(x) => x.remaining(),
+			SerdeArray::Serializing(_) => None
+		}
+	}
+	/// Gets an item, unpacking it from the previous booleans if enabled
+	pub(crate) fn get_item<T: GetDatumType>(&mut self) -> Result<Datum, DeserializationError> {
+		self.get_item_sized::<T>(DatumSize::U32)
+	}
+	/// Gets an item whose length is written as an integer of the given size,
+	/// unpacking it from the previous booleans if enabled
+	fn get_item_sized<T: GetDatumType>(&mut self, size: DatumSize) -> Result<Datum, DeserializationError> {
+		if !matches!(T::get_datum_type(), DatumType::Bool) || !self.options.pack_bools {
+			self.packed_bits = 0;
+			return self.data.get_item_sized::<T>(size)
+		}
+		if self.packed_bits == 0 || self.packed_bits == 8 {
+			self.packed_byte = self.data.get_item::<u8>()?.try_into()?;
+			self.packed_bits = 0;
+		}
+		let bit = (self.packed_byte >> self.packed_bits) & 1;
+		self.packed_bits += 1;
+		Ok(Datum::Bool(bit == 1))
+	}
+	/// Returns the options used to lay out items
+	pub fn options(&self) -> ArrayOptions {
+		self.options
+	}
+	/// Sets the options used to lay out items.
+	/// This should be done before any items are serialized or deserialized
+	pub fn set_options(&mut self, options: ArrayOptions) {
+		self.options = options;
+		if let SerdeArray::Deserializing(x) = &mut self.data {
+			x.set_options(options);
+		}
+	}
+	/// Returns a copy of the items that have been serialized, or that have not been deserialized yet.
+	/// Returns None if the source does not know how many items are left, such as a binary source, as its items cannot be listed
+	pub(crate) fn items(&self) -> Option<Vec<Datum>> {
+		match &self.data {
+			SerdeArray::Serializing(x) => Some(x.iter().cloned().map(|item| item.into_datum().0).collect()),
+			SerdeArray::Deserializing(x) => x.remaining().map(|_| take_items(x.boxed_clone().as_mut()))
+		}
+	}
+	/// Consumes self and returns an iterator over data and their sizes.
+	/// If this data profile is not in serialization mode, the items that have not been deserialized yet are used instead.
+	///
+	/// Lengths of sequences and maps, and indices of enum variants, are given as integers of their size.
+	/// Lengths that do not fit in their size are given as a u64, which binary formats reject instead
 	#[must_use]
 	pub fn into_serialized_items(self) -> IntoIter<(Datum, DatumSize)> {
+		self.into_items()
+			.map(SerialItem::into_datum)
+			.collect::<Vec<_>>()
+			.into_iter()
+	}
+	/// Consumes self and returns an iterator over the serialized items.
+	/// If this data profile is not in serialization mode, the items that have not been deserialized yet are used instead
+	pub(crate) fn into_items(self) -> IntoIter<SerialItem> {
 		match self.data {
 			SerdeArray::Serializing(x) => x.into_iter(),
-			SerdeArray::Deserializing(_) => panic!("Attempted to iterate through items while deserializing")
+			SerdeArray::Deserializing(mut x) => take_items(x.as_mut())
+				.into_iter()
+				.map(|datum| SerialItem::Datum(datum, DatumSize::U32))
+				.collect::<Vec<_>>()
+				.into_iter()
 		}
 	}
 	/// Serializes the given item that can turn into a Datum
 	pub fn serialize_item<T: Into<Datum>>(&mut self, item: T) {
-		self.data.push_item(item);
+		self.push_item(item.into());
 	}
 	/// Deserializes some data and places it in into
 	pub fn deserialize_item<T, E>(&mut self, into: &mut T) -> Result<(), DeserializationError>
@@ -77,7 +401,8 @@ impl ArrayData {
 			T: TryFrom<Datum, Error=E> + GetDatumType,
 			DeserializationError: From<E>
 	{
-		*into = TryFrom::try_from(self.data.get_item::<T>()?)?;
+		let path = self.next_path();
+		*into = self.read().transform(path)?;
 		Ok(())
 	}
 	pub fn deserialize_matched_item<T, I, Iter>(&mut self, into: &mut T, matches: I) -> Result<(), DeserializationError>
@@ -86,7 +411,8 @@ impl ArrayData {
 			I: IntoIterator<IntoIter=Iter>,
 			Iter: Iterator<Item=T>
 	{
-		let item = self.data.get_item::<T>()?;
+		let path = self.next_path();
+		let item = self.get_item::<T>().transform(path.clone())?;
 
 		for maybe_match in matches {
 			if maybe_match.eq(&item) {
@@ -97,7 +423,7 @@ impl ArrayData {
 
 		let mut debug_string = String::new();
 		let _ = writeln!(&mut debug_string, "{:?}", item);
-		Err(DeserializationError::NoMatch { field: "".into(), actual: debug_string })
+		Err(DeserializationError::NoMatch { field: path, actual: debug_string })
 	}
 	pub fn deserialize_cloned_matched_item<'a, T, I, Iter>(&mut self, into: &mut T, matches: I) -> Result<(), DeserializationError>
 		where
@@ -105,7 +431,8 @@ impl ArrayData {
 			I: IntoIterator<IntoIter=Iter>,
 			Iter: Iterator<Item=&'a T>
 	{
-		let item = self.data.get_item::<T>()?;
+		let path = self.next_path();
+		let item = self.get_item::<T>().transform(path.clone())?;
 
 		for maybe_match in matches {
 			if maybe_match.eq(&item) {
@@ -116,7 +443,7 @@ impl ArrayData {
 
 		let mut debug_string = String::new();
 		let _ = writeln!(&mut debug_string, "{:?}", item);
-		Err(DeserializationError::NoMatch { field: "".into(), actual: debug_string })
+		Err(DeserializationError::NoMatch { field: path, actual: debug_string })
 	}
 	/// Serializes or deserializes, based on the current state
 	pub fn serde_item<T, E>(&mut self, into: &mut T) -> Result<(), DeserializationError>
@@ -130,6 +457,153 @@ impl ArrayData {
 		}
 		self.deserialize_item(into)
 	}
+	/// Serializes an item, writing the length of strings, maps and arrays as an integer of the given size.
+	/// The size is ignored if varints are enabled.
+	/// Lengths are only written by binary formats, which return a SerializationError if the length does not fit
+	pub fn serialize_item_sized<T: Into<Datum>>(&mut self, item: T, size: DatumSize) {
+		self.push_item_sized(item.into(), size);
+	}
+	/// Deserializes an item whose length was written as an integer of the given size
+	pub fn deserialize_item_sized<T, E>(&mut self, into: &mut T, size: DatumSize) -> Result<(), DeserializationError>
+		where
+			T: TryFrom<Datum, Error=E> + GetDatumType,
+			DeserializationError: From<E>
+	{
+		let path = self.next_path();
+		*into = self.read_sized(size).transform(path)?;
+		Ok(())
+	}
+	/// Serializes or deserializes an item whose length is written as an integer of the given size, based on the current state
+	pub fn serde_item_sized<T, E>(&mut self, into: &mut T, size: DatumSize) -> Result<(), DeserializationError>
+		where
+			T: Into<Datum> + TryFrom<Datum, Error=E> + Default + GetDatumType,
+			DeserializationError: From<E>
+	{
+		if self.is_serial() {
+			self.serialize_item_sized(take(into), size);
+			return Ok(())
+		}
+		self.deserialize_item_sized(into, size)
+	}
+	/// Serializes an item that may be missing.
+	/// A boolean is written first to mark if the item is present
+	pub fn serialize_optional_item<T: Into<Datum>>(&mut self, item: Option<T>) {
+		self.serialize_item(item.is_some());
+		if let Some(item) = item {
+			self.serialize_item(item);
+		}
+	}
+	/// Deserializes an item that may be missing
+	pub fn deserialize_optional_item<T, E>(&mut self, into: &mut Option<T>) -> Result<(), DeserializationError>
+		where
+			T: TryFrom<Datum, Error=E> + GetDatumType,
+			DeserializationError: From<E>
+	{
+		let path = self.next_path();
+		let present = self.read::<bool, _>().transform(path.clone())?;
+		*into = if present {
+			Some(self.read().transform(path)?)
+		} else {
+			None
+		};
+		Ok(())
+	}
+	/// Serializes or deserializes an item that may be missing, based on the current state
+	pub fn serde_optional_item<T, E>(&mut self, into: &mut Option<T>) -> Result<(), DeserializationError>
+		where
+			T: Into<Datum> + TryFrom<Datum, Error=E> + GetDatumType,
+			DeserializationError: From<E>
+	{
+		if self.is_serial() {
+			self.serialize_optional_item(into.take());
+			return Ok(())
+		}
+		self.deserialize_optional_item(into)
+	}
+	/// Serializes a sequence of items.
+	/// The length of the sequence is written first
+	pub fn serialize_seq_item<T: Into<Datum>>(&mut self, items: Vec<T>) {
+		self.push_count(items.len(), DatumSize::U32);
+		for item in items {
+			self.serialize_item(item);
+		}
+	}
+	/// Deserializes a sequence of items into a vector
+	pub fn deserialize_seq_item<T, E>(&mut self, into: &mut Vec<T>) -> Result<(), DeserializationError>
+		where
+			T: TryFrom<Datum, Error=E> + GetDatumType,
+			DeserializationError: From<E>
+	{
+		let path = self.next_path();
+		let len = self.get_len(DatumSize::U32).transform(path.clone())?;
+		into.clear();
+		for index in 0..len {
+			into.push(self.read().transform(format!("{}[{}]", path, index))?);
+		}
+		Ok(())
+	}
+	/// Serializes or deserializes a sequence of items, based on the current state
+	pub fn serde_seq_item<T, E>(&mut self, into: &mut Vec<T>) -> Result<(), DeserializationError>
+		where
+			T: Into<Datum> + TryFrom<Datum, Error=E> + GetDatumType,
+			DeserializationError: From<E>
+	{
+		if self.is_serial() {
+			self.serialize_seq_item(take(into));
+			return Ok(())
+		}
+		self.deserialize_seq_item(into)
+	}
+	/// Serializes a map, such as a HashMap or BTreeMap.
+	/// The number of entries is written first, followed by the key and value of each entry
+	pub fn serialize_map_item<K, V, M>(&mut self, map: M)
+		where
+			K: Into<Datum>,
+			V: Into<Datum>,
+			M: IntoIterator<Item=(K, V)>,
+			M::IntoIter: ExactSizeIterator
+	{
+		let entries = map.into_iter();
+		self.push_count(entries.len(), DatumSize::U32);
+		for (key, value) in entries {
+			self.serialize_item(key);
+			self.serialize_item(value);
+		}
+	}
+	/// Deserializes a map, replacing the contents of into
+	pub fn deserialize_map_item<K, V, M, KE, VE>(&mut self, into: &mut M) -> Result<(), DeserializationError>
+		where
+			K: TryFrom<Datum, Error=KE> + GetDatumType,
+			V: TryFrom<Datum, Error=VE> + GetDatumType,
+			M: Extend<(K, V)> + Default,
+			DeserializationError: From<KE> + From<VE>
+	{
+		let path = self.next_path();
+		let len = self.get_len(DatumSize::U32).transform(path.clone())?;
+		*into = M::default();
+		for index in 0..len {
+			let entry_path = format!("{}[{}]", path, index);
+			let key = self.read().transform(entry_path.clone())?;
+			let value = self.read().transform(entry_path)?;
+			into.extend(once((key, value)));
+		}
+		Ok(())
+	}
+	/// Serializes or deserializes a map, based on the current state
+	pub fn serde_map_item<K, V, M, KE, VE>(&mut self, into: &mut M) -> Result<(), DeserializationError>
+		where
+			K: Into<Datum> + TryFrom<Datum, Error=KE> + GetDatumType,
+			V: Into<Datum> + TryFrom<Datum, Error=VE> + GetDatumType,
+			M: IntoIterator<Item=(K, V)> + Extend<(K, V)> + Default,
+			M::IntoIter: ExactSizeIterator,
+			DeserializationError: From<KE> + From<VE>
+	{
+		if self.is_serial() {
+			self.serialize_map_item(take(into));
+			return Ok(())
+		}
+		self.deserialize_map_item(into)
+	}
 	pub fn serde_matched_item<T, I, Iter>(&mut self, into: &mut T, matches: I) -> Result<(), DeserializationError>
 		where
 			T: Into<Datum> + Default + GetDatumType + PartialEq<Datum>,
@@ -162,6 +636,221 @@ impl<D: DatumArray + 'static> ProfileFromData<D> for ArrayData {
 		Ok(Self {
 			serializing: false,
 			data: SerdeArray::Deserializing(Box::new(data)),
+			options: ArrayOptions::default(),
+			packed_bits: 0,
+			packed_byte: 0,
+			index: 0,
+			version: 0,
+			sink: None,
 		})
 	}
 }
+
+
+/// Methods for data profiles that use ArrayData as their base.
+/// This is implemented automatically for all such data profiles
+pub trait ArrayProfile: DataProfile + DerefMut<Target=ArrayData> + Sized {
+	/// Serializes a value that implements Serde with this profile.
+	/// The items of the value are placed inline, as if they were items of self.
+	/// Returns any error that the Serde implementation of the value returns
+	fn serialize_nested_item<T: Serde<Self>>(&mut self, mut item: T) -> Result<(), DeserializationError> {
+		let mut profile = Self::serial_ready();
+		serde_value(&mut item, &mut profile)?;
+		let mut nested = ArrayData::serial_ready();
+		swap(&mut nested, &mut profile);
+		self.push_nested(nested);
+		Ok(())
+	}
+	/// Serializes a value that implements SerializeRef with this profile, leaving the value intact.
+	/// The items of the value are placed inline, as if they were items of self
+	fn serialize_nested_item_ref<T: SerializeRef<Self>>(&mut self, item: &T) {
+		let mut nested = ArrayData::serial_ready();
+		swap(&mut nested, &mut item.to_data_profile());
+		self.push_nested(nested);
+	}
+	/// Deserializes a value that implements Serde with this profile.
+	/// The items of the value are read from self
+	fn deserialize_nested_item<T: Serde<Self>>(&mut self, into: &mut T) -> Result<(), DeserializationError> {
+		let path = self.next_path();
+		with_nested_profile(self, |profile| serde_value(into, profile)).transform(path)
+	}
+	/// Constructs a value that implements DeserializeNew with this profile.
+	/// The items of the value are read from self
+	fn deserialize_nested_item_new<T: DeserializeNew<Self>>(&mut self) -> Result<T, DeserializationError> {
+		let path = self.next_path();
+		with_nested_profile(self, deserialize_new_value::<Self, T>).transform(path)
+	}
+	/// Serializes or deserializes a nested value, based on the current state.
+	/// The value must implement Serde with this profile
+	fn serde_nested_item<T: Serde<Self> + Default>(&mut self, into: &mut T) -> Result<(), DeserializationError> {
+		if self.is_serial() {
+			return self.serialize_nested_item(take(into))
+		}
+		self.deserialize_nested_item(into)
+	}
+	/// Serializes a sequence of values that implement Serde with this profile.
+	/// The length of the sequence is written first, followed by the items of each value.
+	/// Returns the first error that the Serde implementation of a value returns
+	fn serialize_nested_seq_item<T: Serde<Self>>(&mut self, items: Vec<T>) -> Result<(), DeserializationError> {
+		self.push_count(items.len(), DatumSize::U32);
+		for item in items {
+			self.serialize_nested_item(item)?;
+		}
+		Ok(())
+	}
+	/// Serializes a sequence of values that implement SerializeRef with this profile, leaving the values intact
+	fn serialize_nested_seq_item_ref<T: SerializeRef<Self>>(&mut self, items: &[T]) {
+		self.push_count(items.len(), DatumSize::U32);
+		for item in items {
+			self.serialize_nested_item_ref(item);
+		}
+	}
+	/// Deserializes a sequence of values that implement Serde with this profile
+	fn deserialize_nested_seq_item<T: Serde<Self> + Default>(&mut self, into: &mut Vec<T>) -> Result<(), DeserializationError> {
+		let path = self.next_path();
+		let len = self.get_len(DatumSize::U32).transform(path.clone())?;
+		into.clear();
+		for index in 0..len {
+			let mut item = T::default();
+			with_nested_profile(self, |profile| serde_value(&mut item, profile)).transform(format!("{}[{}]", path, index))?;
+			into.push(item);
+		}
+		Ok(())
+	}
+	/// Serializes or deserializes a sequence of nested values, based on the current state
+	fn serde_nested_seq_item<T: Serde<Self> + Default>(&mut self, into: &mut Vec<T>) -> Result<(), DeserializationError> {
+		if self.is_serial() {
+			return self.serialize_nested_seq_item(take(into))
+		}
+		self.deserialize_nested_seq_item(into)
+	}
+	/// Serializes a map whose values implement Serde with this profile.
+	/// The number of entries is written first, followed by the key and the items of the value of each entry.
+	/// Returns the first error that the Serde implementation of a value returns
+	fn serialize_nested_map_item<K, T, M>(&mut self, map: M) -> Result<(), DeserializationError>
+		where
+			K: Into<Datum>,
+			T: Serde<Self>,
+			M: IntoIterator<Item=(K, T)>,
+			M::IntoIter: ExactSizeIterator
+	{
+		let entries = map.into_iter();
+		self.push_count(entries.len(), DatumSize::U32);
+		for (key, value) in entries {
+			self.serialize_item(key);
+			self.serialize_nested_item(value)?;
+		}
+		Ok(())
+	}
+	/// Serializes a map whose values implement SerializeRef with this profile, leaving the map intact.
+	/// The map is given as an iterator over references, such as &HashMap
+	fn serialize_nested_map_item_ref<'a, K, T, M>(&mut self, map: M)
+		where
+			K: 'a,
+			&'a K: Into<Datum>,
+			T: SerializeRef<Self> + 'a,
+			M: IntoIterator<Item=(&'a K, &'a T)>,
+			M::IntoIter: ExactSizeIterator
+	{
+		let entries = map.into_iter();
+		self.push_count(entries.len(), DatumSize::U32);
+		for (key, value) in entries {
+			self.serialize_item(key);
+			self.serialize_nested_item_ref(value);
+		}
+	}
+	/// Deserializes a map whose values implement Serde with this profile
+	fn deserialize_nested_map_item<K, T, M, KE>(&mut self, into: &mut M) -> Result<(), DeserializationError>
+		where
+			K: TryFrom<Datum, Error=KE> + GetDatumType,
+			T: Serde<Self> + Default,
+			M: Extend<(K, T)> + Default,
+			DeserializationError: From<KE>
+	{
+		let path = self.next_path();
+		let len = self.get_len(DatumSize::U32).transform(path.clone())?;
+		*into = M::default();
+		for index in 0..len {
+			let entry_path = format!("{}[{}]", path, index);
+			let key = self.read().transform(entry_path.clone())?;
+			let mut value = T::default();
+			with_nested_profile(self, |profile| serde_value(&mut value, profile)).transform(entry_path)?;
+			into.extend(once((key, value)));
+		}
+		Ok(())
+	}
+	/// Serializes or deserializes a map of nested values, based on the current state
+	fn serde_nested_map_item<K, T, M, KE>(&mut self, into: &mut M) -> Result<(), DeserializationError>
+		where
+			K: Into<Datum> + TryFrom<Datum, Error=KE> + GetDatumType,
+			T: Serde<Self> + Default,
+			M: IntoIterator<Item=(K, T)> + Extend<(K, T)> + Default,
+			M::IntoIter: ExactSizeIterator,
+			DeserializationError: From<KE>
+	{
+		if self.is_serial() {
+			return self.serialize_nested_map_item(take(into))
+		}
+		self.deserialize_nested_map_item(into)
+	}
+	/// Serializes an enum whose variants carry data.
+	/// The index of the variant is written first, using the smallest integer that fits all indices,
+	/// followed by the items of the fields.
+	/// Returns any error that the Serde implementation of the enum returns
+	fn serialize_enum_item<T: Serde<Self> + EnumVariants>(&mut self, item: T) -> Result<(), DeserializationError> {
+		self.push_count(item.variant_index(), variant_index_size(T::VARIANTS.len()));
+		self.serialize_nested_item(item)
+	}
+	/// Serializes an enum whose variants carry data, leaving the enum intact
+	fn serialize_enum_item_ref<T: SerializeRef<Self> + EnumVariants>(&mut self, item: &T) {
+		self.push_count(item.variant_index(), variant_index_size(T::VARIANTS.len()));
+		self.serialize_nested_item_ref(item);
+	}
+	/// Deserializes an enum whose variants carry data
+	fn deserialize_enum_item<T: Serde<Self> + EnumVariants>(&mut self, into: &mut T) -> Result<(), DeserializationError> {
+		let path = self.next_path();
+		let index = self.get_len(variant_index_size(T::VARIANTS.len())).transform(path.clone())?;
+		*into = T::from_variant_index(index)
+			.ok_or_else(|| DeserializationError::NoMatch { field: path.clone(), actual: index.to_string() })?;
+		with_nested_profile(self, |profile| serde_value(into, profile)).transform(path)
+	}
+	/// Serializes or deserializes an enum whose variants carry data, based on the current state
+	fn serde_enum_item<T: Serde<Self> + EnumVariants + Default>(&mut self, into: &mut T) -> Result<(), DeserializationError> {
+		if self.is_serial() {
+			return self.serialize_enum_item(take(into))
+		}
+		self.deserialize_enum_item(into)
+	}
+}
+
+
+/// Runs the given deserialization against a nested profile that reads from the source of parent
+fn with_nested_profile<P, R, F>(parent: &mut P, f: F) -> Result<R, DeserializationError>
+	where
+		P: ArrayProfile,
+		F: FnOnce(&mut P) -> Result<R, DeserializationError>
+{
+	let mut profile = P::serial_ready();
+	profile.serializing = false;
+	// Lend our source to the nested profile for the duration of its deserialization
+	swap(&mut profile.data, &mut parent.data);
+	let result = f(&mut profile);
+	swap(&mut profile.data, &mut parent.data);
+	parent.packed_bits = 0;
+	result
+}
+
+
+/// Returns the smallest size that can hold the index of any of the given number of variants
+fn variant_index_size(variants: usize) -> DatumSize {
+	if variants <= u8::MAX as usize + 1 {
+		DatumSize::U8
+	} else if variants <= u16::MAX as usize + 1 {
+		DatumSize::U16
+	} else {
+		DatumSize::U32
+	}
+}
+
+
+impl<P: DataProfile + DerefMut<Target=ArrayData>> ArrayProfile for P {}
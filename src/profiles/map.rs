@@ -1,48 +1,99 @@
 use std::collections::hash_map::IntoIter;
 use std::collections::HashMap;
 use std::fmt::{Debug, Write};
-use std::mem::{take};
+use std::iter::once;
+use std::mem::{swap, take};
+use std::ops::DerefMut;
+use std::str::FromStr;
 
-use crate::{DeserializationError, ProfileFromData, TransformResult};
-use crate::datum::{Datum};
+use crate::{DeserializationError, DeserializeNew, EnumTagging, EnumVariants, ProfileFromData, Serde, SerializeRef, TransformResult, deserialize_new_value, join_path, serde_value};
+use crate::ArrayData;
+use crate::datum::{Datum, GetDatumType, parse_key};
 use crate::profiles::SerdeData;
 
 use super::DataProfile;
 
 pub trait DatumMap: Debug {
 	fn get_datum(&mut self, key: &Datum) -> Result<Datum, DeserializationError>;
+	/// Returns the keys of all entries that have not been taken yet
+	fn remaining_keys(&self) -> Vec<Datum>;
+	/// Clones the entries that have not been taken yet into a new source
+	fn boxed_clone(&self) -> Box<dyn DatumMap>;
 }
 
 type SerdeMap = SerdeData<HashMap<Datum, Datum>, dyn DatumMap>;
 
 
+impl Clone for SerdeMap {
+	fn clone(&self) -> Self {
+		match self {
+			Self::Deserializing(x) => Self::Deserializing(x.boxed_clone()),
+			Self::Serializing(x) => Self::Serializing(x.clone())
+		}
+	}
+}
+
+
 impl SerdeMap {
+	/// Sets an entry. Does nothing while deserializing
 	fn set(&mut self, key: Datum, value: Datum) {
-		match self {
-			Self::Deserializing(_) => panic!("Attempted to set while deserializing! Please report this to the developer"),
-			Self::Serializing(x) => x.insert(key, value)
-		};
+		if let Self::Serializing(x) = self {
+			x.insert(key, value);
+		}
 	}
-	
+	/// Gets an entry. There are no entries to get while serializing
 	fn get(&mut self, key: &Datum) -> Result<Datum, DeserializationError> {
 		match self {
 			Self::Deserializing(x) => x.get_datum(key),
-			Self::Serializing(_) => panic!("Attempted to get while serializing! Please report this to the developer")
+			Self::Serializing(_) => Err(DeserializationError::MissingField(key.to_key_string()))
 		}
 	}
 }
 
 
+/// Takes every remaining entry out of the given source.
+/// Entries that cannot be read are skipped
+fn take_entries(source: &mut dyn DatumMap) -> HashMap<Datum, Datum> {
+	source.remaining_keys()
+		.into_iter()
+		.filter_map(|key| {
+			let value = source.get_datum(&key).ok()?;
+			Some((key, value))
+		})
+		.collect()
+}
+
+
 /// A base data profile for data that is stored in a map, with keys and values.
 /// Keys are always static strings.
 /// You will not instantiate this directly, but you will make aliases of this using the make_data_profile macro
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct MappedData {
 	serializing: bool,
 	data: SerdeMap,
+	options: MappedOptions,
+	version: u8,
 }
 
 
+/// Options that change how the entries of a MappedData are written.
+/// These can be set for each data profile through the make_data_profile macro
+#[derive(Debug, Copy, Clone, Default)]
+pub struct MappedOptions {
+	/// Leaves out entries that equal their default when serializing with serde_entry_or or serde_entry_or_default,
+	/// which keeps generated files minimal. They are filled back in from the default when deserializing
+	pub skip_defaults: bool,
+	/// Returns an UnknownField error if entries are left over once a value has been deserialized,
+	/// rather than ignoring them. Use serde_remaining_entries to keep them instead
+	pub deny_unknown: bool,
+}
+
+
+/// The reserved key that holds the version of a map.
+/// It is only written for versions other than 0, so maps without it are read as version 0
+pub const VERSION_KEY: &str = "__version";
+
+
 impl DataProfile for MappedData {
 	fn is_serial(&self) -> bool {
 		self.serializing
@@ -51,20 +102,98 @@ impl DataProfile for MappedData {
 		Self {
 			serializing: true,
 			data: SerdeMap::Serializing(HashMap::new()),
+			options: MappedOptions::default(),
+			version: 0,
+		}
+	}
+	fn version(&self) -> u8 {
+		self.version
+	}
+	fn apply_version(&mut self, current: u8) -> Result<(), DeserializationError> {
+		if self.serializing {
+			self.version = current;
+			if current != 0 {
+				self.data.set(VERSION_KEY.into(), current.into());
+			}
+			return Ok(())
+		}
+		self.version = match self.data.get(&VERSION_KEY.into()) {
+			Ok(x) => u8::try_from(x).transform(VERSION_KEY.into())?,
+			Err(DeserializationError::MissingField(_)) => 0,
+			Err(e) => return Err(e)
+		};
+		if self.version > current {
+			return Err(DeserializationError::UnsupportedVersion { field: VERSION_KEY.into(), version: self.version, latest: current })
+		}
+		Ok(())
+	}
+	fn finish(&mut self) -> Result<(), DeserializationError> {
+		if self.serializing || !self.options.deny_unknown {
+			return Ok(())
+		}
+		let mut keys: Vec<String> = self.remaining_keys().iter().map(Datum::to_key_string).collect();
+		if keys.is_empty() {
+			return Ok(())
 		}
+		keys.sort();
+		Err(DeserializationError::UnknownField { field: String::new(), keys })
 	}
 }
 
 
 impl MappedData {
-	/// Converts this data profile into an iterator over serialized entries
-	///
-	/// # panic
-	/// Panics if this data profile is not in serialization mode
+	/// Returns true if no entries have been serialized
+	fn is_empty(&self) -> bool {
+		match &self.data {
+			SerdeMap::Serializing(x) => x.is_empty(),
+			SerdeMap::Deserializing(_) => self.remaining_keys().is_empty()
+		}
+	}
+	/// Returns the keys of the entries that have not been deserialized yet
+	fn remaining_keys(&self) -> Vec<Datum> {
+		match &self.data {
+			SerdeMap::Deserializing(x) => x.remaining_keys(),
+			SerdeMap::Serializing(_) => Vec::new()
+		}
+	}
+	/// Gets a map entry, along with the keys in it
+	fn get_map(&mut self, name: &Datum) -> Result<(MappedData, Vec<Datum>), DeserializationError> {
+		let map = expect_map(self.data.get(name)?, &name.to_key_string())?;
+		let keys = map.remaining_keys();
+		Ok((map, keys))
+	}
+	/// Gets a sequence entry, along with the number of items in it
+	fn get_seq(&mut self, name: &Datum) -> Result<(ArrayData, usize), DeserializationError> {
+		let invalid = |actual| DeserializationError::InvalidType { field: name.to_key_string(), expected: "array", actual };
+		match self.data.get(name)? {
+			Datum::Array(x) => {
+				let len = x.remaining().ok_or_else(|| invalid("array of unknown length"))?;
+				Ok((x, len))
+			}
+			x => Err(invalid(x.type_name()))
+		}
+	}
+	/// Returns a copy of the entries that have been serialized, or that have not been deserialized yet
+	pub(crate) fn entries(&self) -> HashMap<Datum, Datum> {
+		match &self.data {
+			SerdeMap::Serializing(x) => x.clone(),
+			SerdeMap::Deserializing(x) => take_entries(x.boxed_clone().as_mut())
+		}
+	}
+	/// Returns the options used to write entries
+	pub fn options(&self) -> MappedOptions {
+		self.options
+	}
+	/// Sets the options used to write entries
+	pub fn set_options(&mut self, options: MappedOptions) {
+		self.options = options;
+	}
+	/// Converts this data profile into an iterator over serialized entries.
+	/// If this data profile is not in serialization mode, the entries that have not been deserialized yet are used instead
 	#[must_use]
 	pub fn into_serialized_entries(self) -> IntoIter<Datum, Datum> {
 		match self.data {
-			SerdeMap::Deserializing(_) => panic!("Attempted to iterate through entries while deserializing"),
+			SerdeMap::Deserializing(mut x) => take_entries(x.as_mut()).into_iter(),
 			SerdeMap::Serializing(x) => x.into_iter()
 		}
 	}
@@ -143,7 +272,228 @@ impl MappedData {
 			self.serialize_entry(name.into(), take(value));
 			return Ok(());
 		}
-		self.deserialize_entry(name, value).map_err(Into::into)
+		self.deserialize_entry(name, value)
+	}
+	/// Deserialize a named entry, using the given default if the entry is missing
+	pub fn deserialize_entry_or<K, V, E>(&mut self, name: K, into: &mut V, default: V) -> Result<(), DeserializationError>
+		where
+			K: Into<Datum>,
+			V: TryFrom<Datum, Error=E>,
+			DeserializationError: From<E>
+	{
+		let name_ref = name.into();
+		*into = match self.data.get(&name_ref) {
+			Ok(x) => x.try_into().transform(name_ref.to_key_string())?,
+			Err(DeserializationError::MissingField(_)) => default,
+			Err(e) => return Err(e)
+		};
+		Ok(())
+	}
+	/// Deserialize a named entry, using the default value of the type if the entry is missing
+	pub fn deserialize_entry_or_default<K, V, E>(&mut self, name: K, into: &mut V) -> Result<(), DeserializationError>
+		where
+			K: Into<Datum>,
+			V: TryFrom<Datum, Error=E> + Default,
+			DeserializationError: From<E>
+	{
+		self.deserialize_entry_or(name, into, V::default())
+	}
+	/// Either serializes or deserializes a named entry that falls back to the given default when it is missing.
+	/// If skip_defaults is set, the entry is not written when the value equals the default
+	pub fn serde_entry_or<K, V, E>(&mut self, name: K, value: &mut V, default: V) -> Result<(), DeserializationError>
+		where
+			K: Into<Datum>,
+			V: Into<Datum> + TryFrom<Datum, Error=E> + Default + PartialEq,
+			DeserializationError: From<E>
+	{
+		if self.serializing {
+			if !self.options.skip_defaults || *value != default {
+				self.serialize_entry(name, take(value));
+			}
+			return Ok(());
+		}
+		self.deserialize_entry_or(name, value, default)
+	}
+	/// Either serializes or deserializes a named entry that falls back to the default value of the type when it is missing.
+	/// If skip_defaults is set, the entry is not written when the value equals the default
+	pub fn serde_entry_or_default<K, V, E>(&mut self, name: K, value: &mut V) -> Result<(), DeserializationError>
+		where
+			K: Into<Datum>,
+			V: Into<Datum> + TryFrom<Datum, Error=E> + Default + PartialEq,
+			DeserializationError: From<E>
+	{
+		self.serde_entry_or(name, value, V::default())
+	}
+	/// Serialize a named value as an entry, if there is a value.
+	/// Nothing is written if the value is None
+	pub fn serialize_optional_entry<K: Into<Datum>, V: Into<Datum>>(&mut self, name: K, value: Option<V>) {
+		if let Some(value) = value {
+			self.serialize_entry(name, value);
+		}
+	}
+	/// Deserialize a named entry that may be missing.
+	/// into is set to None if the entry is missing or null
+	pub fn deserialize_optional_entry<K, V, E>(&mut self, name: K, into: &mut Option<V>) -> Result<(), DeserializationError>
+		where
+			K: Into<Datum>,
+			V: TryFrom<Datum, Error=E>,
+			DeserializationError: From<E>
+	{
+		let name_ref = name.into();
+		*into = match self.data.get(&name_ref) {
+			Ok(x) => Some(x.try_into().transform(name_ref.to_key_string())?),
+			Err(DeserializationError::MissingField(_)) => None,
+			Err(e) => return Err(e)
+		};
+		Ok(())
+	}
+	/// Either serializes or deserializes a named entry that may be missing.
+	/// The entry is omitted during serialization if the value is None
+	pub fn serde_optional_entry<K, V, E>(&mut self, name: K, value: &mut Option<V>) -> Result<(), DeserializationError>
+		where
+			K: Into<Datum>,
+			V: Into<Datum> + TryFrom<Datum, Error=E>,
+			DeserializationError: From<E>
+	{
+		if self.serializing {
+			self.serialize_optional_entry(name, value.take());
+			return Ok(());
+		}
+		self.deserialize_optional_entry(name, value)
+	}
+	/// Serialize a sequence of values as a named entry
+	pub fn serialize_seq_entry<K: Into<Datum>, V: Into<Datum>>(&mut self, name: K, values: Vec<V>) {
+		let mut array = ArrayData::serial_ready();
+		for value in values {
+			array.serialize_item(value);
+		}
+		self.serialize_entry(name, Datum::Array(array));
+	}
+	/// Deserialize a named sequence entry into a vector
+	pub fn deserialize_seq_entry<K, V, E>(&mut self, name: K, into: &mut Vec<V>) -> Result<(), DeserializationError>
+		where
+			K: Into<Datum>,
+			V: TryFrom<Datum, Error=E> + GetDatumType,
+			DeserializationError: From<E>
+	{
+		let name_ref = name.into();
+		let field = name_ref.to_key_string();
+		let (mut array, len) = self.get_seq(&name_ref)?;
+		into.clear();
+		for index in 0..len {
+			into.push(array.get_item::<V>()?.try_into().transform(format!("{}[{}]", field, index))?);
+		}
+		Ok(())
+	}
+	/// Either serializes or deserializes a named sequence entry.
+	/// The items of the sequence must be able to convert to or from a Datum
+	pub fn serde_seq_entry<K, V, E>(&mut self, name: K, value: &mut Vec<V>) -> Result<(), DeserializationError>
+		where
+			K: Into<Datum>,
+			V: Into<Datum> + TryFrom<Datum, Error=E> + GetDatumType,
+			DeserializationError: From<E>
+	{
+		if self.serializing {
+			self.serialize_seq_entry(name, take(value));
+			return Ok(());
+		}
+		self.deserialize_seq_entry(name, value)
+	}
+	/// Serialize a map, such as a HashMap or BTreeMap, as a named entry.
+	/// Text formats only support string keys, so other keys are written with Datum::to_key_string
+	pub fn serialize_map_entry<N, K, V, M>(&mut self, name: N, map: M)
+		where
+			N: Into<Datum>,
+			K: Into<Datum>,
+			V: Into<Datum>,
+			M: IntoIterator<Item=(K, V)>
+	{
+		let mut nested = MappedData::serial_ready();
+		for (key, value) in map {
+			nested.serialize_entry(key, value);
+		}
+		self.serialize_entry(name, Datum::Map(nested));
+	}
+	/// Deserialize a named map entry, replacing the contents of into.
+	/// Keys that were written as strings are parsed back with FromStr
+	pub fn deserialize_map_entry<N, K, V, M, KE, VE>(&mut self, name: N, into: &mut M) -> Result<(), DeserializationError>
+		where
+			N: Into<Datum>,
+			K: TryFrom<Datum, Error=KE> + FromStr,
+			V: TryFrom<Datum, Error=VE>,
+			M: Extend<(K, V)> + Default,
+			DeserializationError: From<KE> + From<VE>
+	{
+		let name_ref = name.into();
+		let field = name_ref.to_key_string();
+		let (mut nested, keys) = self.get_map(&name_ref)?;
+		*into = M::default();
+		for key in keys {
+			let value = nested.data.get(&key).transform(field.clone())?
+				.try_into().transform(join_path(&field, &key.to_key_string()))?;
+			into.extend(once((parse_key(key).transform(field.clone())?, value)));
+		}
+		Ok(())
+	}
+	/// Either serializes or deserializes a named map entry.
+	/// The keys and values of the map must be able to convert to or from a Datum
+	pub fn serde_map_entry<N, K, V, M, KE, VE>(&mut self, name: N, value: &mut M) -> Result<(), DeserializationError>
+		where
+			N: Into<Datum>,
+			K: Into<Datum> + TryFrom<Datum, Error=KE> + FromStr,
+			V: Into<Datum> + TryFrom<Datum, Error=VE>,
+			M: IntoIterator<Item=(K, V)> + Extend<(K, V)> + Default,
+			DeserializationError: From<KE> + From<VE>
+	{
+		if self.serializing {
+			self.serialize_map_entry(name, take(value));
+			return Ok(());
+		}
+		self.deserialize_map_entry(name, value)
+	}
+	/// Serialize every entry of a map as an entry of self,
+	/// such as the entries that were collected by deserialize_remaining_entries
+	pub fn serialize_remaining_entries<K, V, M>(&mut self, map: M)
+		where
+			K: Into<Datum>,
+			V: Into<Datum>,
+			M: IntoIterator<Item=(K, V)>
+	{
+		for (key, value) in map {
+			self.serialize_entry(key, value);
+		}
+	}
+	/// Deserialize every entry that has not been deserialized yet into a map, replacing its contents.
+	/// Call this after every other entry, to keep unknown entries in a catch-all field
+	pub fn deserialize_remaining_entries<K, V, M, KE, VE>(&mut self, into: &mut M) -> Result<(), DeserializationError>
+		where
+			K: TryFrom<Datum, Error=KE> + FromStr,
+			V: TryFrom<Datum, Error=VE>,
+			M: Extend<(K, V)> + Default,
+			DeserializationError: From<KE> + From<VE>
+	{
+		*into = M::default();
+		for key in self.remaining_keys() {
+			let field = key.to_key_string();
+			let value = self.data.get(&key)?.try_into().transform(field)?;
+			into.extend(once((parse_key(key)?, value)));
+		}
+		Ok(())
+	}
+	/// Either serializes or deserializes the entries that are not read by any other method, such as unknown keys.
+	/// This must come after every other entry
+	pub fn serde_remaining_entries<K, V, M, KE, VE>(&mut self, value: &mut M) -> Result<(), DeserializationError>
+		where
+			K: Into<Datum> + TryFrom<Datum, Error=KE> + FromStr,
+			V: Into<Datum> + TryFrom<Datum, Error=VE>,
+			M: IntoIterator<Item=(K, V)> + Extend<(K, V)> + Default,
+			DeserializationError: From<KE> + From<VE>
+	{
+		if self.serializing {
+			self.serialize_remaining_entries(take(value));
+			return Ok(());
+		}
+		self.deserialize_remaining_entries(value)
 	}
 	/// Either serializes or deserializes a named entry that can only be an item in matches.
 	/// Note that the value only needs to be present in matches during deserialization.
@@ -187,6 +537,341 @@ impl<D: DatumMap + 'static> ProfileFromData<D> for MappedData {
 		Ok(Self {
 			serializing: false,
 			data: SerdeMap::Deserializing(Box::new(data)),
+			options: MappedOptions::default(),
+			version: 0,
 		})
 	}
 }
+
+
+/// Methods for data profiles that use MappedData as their base.
+/// This is implemented automatically for all such data profiles
+pub trait MappedProfile: DataProfile + DerefMut<Target=MappedData> + Sized {
+	/// Serialize a value that implements Serde with this profile as a nested entry.
+	/// Returns any error that the Serde implementation of the value returns
+	fn serialize_nested_entry<K: Into<Datum>, T: Serde<Self>>(&mut self, name: K, value: T) -> Result<(), DeserializationError> {
+		let name_ref = name.into();
+		let nested = serialize_nested::<Self, T>(value).transform(name_ref.to_key_string())?;
+		self.serialize_entry(name_ref, Datum::Map(nested));
+		Ok(())
+	}
+	/// Serialize a value that implements SerializeRef with this profile as a nested entry, leaving the value intact
+	fn serialize_nested_entry_ref<K: Into<Datum>, T: SerializeRef<Self>>(&mut self, name: K, value: &T) {
+		let mut nested = MappedData::serial_ready();
+		swap(&mut nested, &mut value.to_data_profile());
+		self.serialize_entry(name, Datum::Map(nested));
+	}
+	/// Deserialize a nested entry into a value that implements Serde with this profile
+	fn deserialize_nested_entry<K: Into<Datum>, T: Serde<Self>>(&mut self, name: K, into: &mut T) -> Result<(), DeserializationError> {
+		let name_ref = name.into();
+		let field = name_ref.to_key_string();
+		let mut profile: Self = nested_profile(self.data.get(&name_ref)?, &field)?;
+		serde_value(into, &mut profile).transform(field)
+	}
+	/// Constructs a value that implements DeserializeNew with this profile from a nested entry
+	fn deserialize_nested_entry_new<K: Into<Datum>, T: DeserializeNew<Self>>(&mut self, name: K) -> Result<T, DeserializationError> {
+		let name_ref = name.into();
+		let field = name_ref.to_key_string();
+		let mut profile: Self = nested_profile(self.data.get(&name_ref)?, &field)?;
+		deserialize_new_value::<Self, T>(&mut profile).transform(field)
+	}
+	/// Either serializes or deserializes a nested entry.
+	/// The value must implement Serde with this profile
+	fn serde_nested_entry<K: Into<Datum>, T: Serde<Self> + Default>(&mut self, name: K, value: &mut T) -> Result<(), DeserializationError> {
+		if self.is_serial() {
+			return self.serialize_nested_entry(name, take(value));
+		}
+		self.deserialize_nested_entry(name, value)
+	}
+	/// Serialize a sequence of values that implement Serde with this profile as a named entry.
+	/// Returns the first error that the Serde implementation of a value returns
+	fn serialize_nested_seq_entry<K: Into<Datum>, T: Serde<Self>>(&mut self, name: K, values: Vec<T>) -> Result<(), DeserializationError> {
+		let name_ref = name.into();
+		let mut array = ArrayData::serial_ready();
+		for (index, value) in values.into_iter().enumerate() {
+			let nested = serialize_nested::<Self, T>(value).transform(format!("{}[{}]", name_ref.to_key_string(), index))?;
+			array.serialize_item(Datum::Map(nested));
+		}
+		self.serialize_entry(name_ref, Datum::Array(array));
+		Ok(())
+	}
+	/// Serialize a sequence of values that implement SerializeRef with this profile as a named entry, leaving the values intact
+	fn serialize_nested_seq_entry_ref<K: Into<Datum>, T: SerializeRef<Self>>(&mut self, name: K, values: &[T]) {
+		let mut array = ArrayData::serial_ready();
+		for value in values {
+			let mut nested = MappedData::serial_ready();
+			swap(&mut nested, &mut value.to_data_profile());
+			array.serialize_item(Datum::Map(nested));
+		}
+		self.serialize_entry(name, Datum::Array(array));
+	}
+	/// Deserialize a named sequence entry into values that implement Serde with this profile
+	fn deserialize_nested_seq_entry<K: Into<Datum>, T: Serde<Self> + Default>(&mut self, name: K, into: &mut Vec<T>) -> Result<(), DeserializationError> {
+		let name_ref = name.into();
+		let field = name_ref.to_key_string();
+		let (mut array, len) = self.get_seq(&name_ref)?;
+		into.clear();
+		for index in 0..len {
+			let path = format!("{}[{}]", field, index);
+			let mut profile: Self = nested_profile(array.get_item::<MappedData>()?, &path)?;
+			let mut value = T::default();
+			serde_value(&mut value, &mut profile).transform(path)?;
+			into.push(value);
+		}
+		Ok(())
+	}
+	/// Either serializes or deserializes a named sequence entry.
+	/// The items of the sequence must implement Serde with this profile
+	fn serde_nested_seq_entry<K: Into<Datum>, T: Serde<Self> + Default>(&mut self, name: K, value: &mut Vec<T>) -> Result<(), DeserializationError> {
+		if self.is_serial() {
+			return self.serialize_nested_seq_entry(name, take(value));
+		}
+		self.deserialize_nested_seq_entry(name, value)
+	}
+	/// Serialize a map whose values implement Serde with this profile as a named entry.
+	/// Returns the first error that the Serde implementation of a value returns
+	fn serialize_nested_map_entry<N, K, T, M>(&mut self, name: N, map: M) -> Result<(), DeserializationError>
+		where
+			N: Into<Datum>,
+			K: Into<Datum>,
+			T: Serde<Self>,
+			M: IntoIterator<Item=(K, T)>
+	{
+		let name_ref = name.into();
+		let field = name_ref.to_key_string();
+		let mut nested = MappedData::serial_ready();
+		for (key, value) in map {
+			let key = key.into();
+			let value_data = serialize_nested::<Self, T>(value).transform(join_path(&field, &key.to_key_string()))?;
+			nested.serialize_entry(key, Datum::Map(value_data));
+		}
+		self.serialize_entry(name_ref, Datum::Map(nested));
+		Ok(())
+	}
+	/// Serialize a map whose values implement SerializeRef with this profile as a named entry, leaving the map intact.
+	/// The map is given as an iterator over references, such as &HashMap
+	fn serialize_nested_map_entry_ref<'a, N, K, T, M>(&mut self, name: N, map: M)
+		where
+			N: Into<Datum>,
+			K: 'a,
+			&'a K: Into<Datum>,
+			T: SerializeRef<Self> + 'a,
+			M: IntoIterator<Item=(&'a K, &'a T)>
+	{
+		let mut nested = MappedData::serial_ready();
+		for (key, value) in map {
+			let mut value_data = MappedData::serial_ready();
+			swap(&mut value_data, &mut value.to_data_profile());
+			nested.serialize_entry(key, Datum::Map(value_data));
+		}
+		self.serialize_entry(name, Datum::Map(nested));
+	}
+	/// Deserialize a named map entry whose values implement Serde with this profile
+	fn deserialize_nested_map_entry<N, K, T, M, KE>(&mut self, name: N, into: &mut M) -> Result<(), DeserializationError>
+		where
+			N: Into<Datum>,
+			K: TryFrom<Datum, Error=KE> + FromStr,
+			T: Serde<Self> + Default,
+			M: Extend<(K, T)> + Default,
+			DeserializationError: From<KE>
+	{
+		let name_ref = name.into();
+		let field = name_ref.to_key_string();
+		let (mut nested, keys) = self.get_map(&name_ref)?;
+		*into = M::default();
+		for key in keys {
+			let path = join_path(&field, &key.to_key_string());
+			let mut profile: Self = nested_profile(nested.data.get(&key).transform(field.clone())?, &path)?;
+			let mut value = T::default();
+			serde_value(&mut value, &mut profile).transform(path)?;
+			into.extend(once((parse_key(key).transform(field.clone())?, value)));
+		}
+		Ok(())
+	}
+	/// Either serializes or deserializes a named map entry.
+	/// The values of the map must implement Serde with this profile
+	fn serde_nested_map_entry<N, K, T, M, KE>(&mut self, name: N, value: &mut M) -> Result<(), DeserializationError>
+		where
+			N: Into<Datum>,
+			K: Into<Datum> + TryFrom<Datum, Error=KE> + FromStr,
+			T: Serde<Self> + Default,
+			M: IntoIterator<Item=(K, T)> + Extend<(K, T)> + Default,
+			DeserializationError: From<KE>
+	{
+		if self.is_serial() {
+			return self.serialize_nested_map_entry(name, take(value));
+		}
+		self.deserialize_nested_map_entry(name, value)
+	}
+	/// Serialize an enum whose variants carry data as a named entry.
+	/// The variant is identified according to tagging.
+	/// Returns any error that the Serde implementation of the enum returns
+	fn serialize_enum_entry<K, T>(&mut self, name: K, value: T, tagging: EnumTagging) -> Result<(), DeserializationError>
+		where
+			K: Into<Datum>,
+			T: Serde<Self> + EnumVariants
+	{
+		let name_ref = name.into();
+		let variant = T::VARIANTS[value.variant_index()];
+		let fields = serialize_nested::<Self, T>(value).transform(name_ref.to_key_string())?;
+		self.serialize_entry(name_ref, tag_enum(variant, fields, tagging));
+		Ok(())
+	}
+	/// Serialize an enum whose variants carry data as a named entry, leaving the enum intact.
+	/// The variant is identified according to tagging
+	fn serialize_enum_entry_ref<K, T>(&mut self, name: K, value: &T, tagging: EnumTagging)
+		where
+			K: Into<Datum>,
+			T: SerializeRef<Self> + EnumVariants
+	{
+		let variant = T::VARIANTS[value.variant_index()];
+		let mut fields = MappedData::serial_ready();
+		swap(&mut fields, &mut value.to_data_profile());
+		self.serialize_entry(name, tag_enum(variant, fields, tagging));
+	}
+	/// Deserialize a named entry into an enum whose variants carry data.
+	/// The variant is identified according to tagging
+	fn deserialize_enum_entry<K, T>(&mut self, name: K, into: &mut T, tagging: EnumTagging) -> Result<(), DeserializationError>
+		where
+			K: Into<Datum>,
+			T: Serde<Self> + EnumVariants
+	{
+		let name_ref = name.into();
+		let field = name_ref.to_key_string();
+		let datum = self.data.get(&name_ref)?;
+		
+		// The path to the map that holds the fields of the variant
+		let (variant, fields, fields_path) = match tagging {
+			EnumTagging::External => match datum {
+				Datum::Map(mut outer) => match outer.remaining_keys().as_slice() {
+					[key] => {
+						let key = key.clone();
+						let fields = outer.data.get(&key)?;
+						let fields_path = join_path(&field, &key.to_key_string());
+						(key, Some(fields), fields_path)
+					}
+					_ => return Err(DeserializationError::InvalidType { field, expected: "map with one entry", actual: "map" })
+				},
+				variant => (variant, None, field.clone())
+			},
+			EnumTagging::Internal(tag) => {
+				let mut fields = expect_map(datum, &field)?;
+				(fields.data.get(&tag.into()).transform(field.clone())?, Some(Datum::Map(fields)), field.clone())
+			}
+			EnumTagging::Adjacent { tag, content } => {
+				let mut outer = expect_map(datum, &field)?;
+				let variant = outer.data.get(&tag.into()).transform(field.clone())?;
+				let fields = match outer.data.get(&content.into()) {
+					Ok(x) => Some(x),
+					Err(DeserializationError::MissingField(_)) => None,
+					Err(e) => return Err(e)
+				};
+				// Entries besides the tag and the content are unknown
+				outer.set_options(self.options());
+				outer.finish().transform(field.clone())?;
+				(variant, fields, join_path(&field, content))
+			}
+			EnumTagging::Untagged => {
+				let fields = expect_map(datum, &field)?;
+				for index in 0..T::VARIANTS.len() {
+					let mut value = match T::from_variant_index(index) {
+						Some(x) => x,
+						None => continue
+					};
+					let mut profile = Self::serial_ready();
+					let options = profile.options();
+					*profile = fields.clone();
+					profile.set_options(options);
+					if serde_value(&mut value, &mut profile).is_ok() {
+						*into = value;
+						return Ok(());
+					}
+				}
+				return Err(DeserializationError::NoMatch { field, actual: "fields that match no variant".into() });
+			}
+		};
+		
+		let variant = String::try_from(variant).transform(field.clone())?;
+		*into = T::VARIANTS.iter()
+			.position(|x| *x == variant)
+			.and_then(T::from_variant_index)
+			.ok_or_else(|| DeserializationError::NoMatch { field: field.clone(), actual: variant })?;
+		
+		let mut profile: Self = match fields {
+			Some(fields) => nested_profile(fields, &fields_path)?,
+			// Variants without fields are still given a chance to deserialize, against no entries
+			None => nested_profile(Datum::Map(ProfileFromData::try_from(HashMap::<String, Datum>::new())?), &fields_path)?
+		};
+		serde_value(into, &mut profile).transform(fields_path)
+	}
+	/// Either serializes or deserializes an enum whose variants carry data as a named entry.
+	/// The variant is identified according to tagging
+	fn serde_enum_entry<K, T>(&mut self, name: K, value: &mut T, tagging: EnumTagging) -> Result<(), DeserializationError>
+		where
+			K: Into<Datum>,
+			T: Serde<Self> + EnumVariants + Default
+	{
+		if self.is_serial() {
+			return self.serialize_enum_entry(name, take(value), tagging);
+		}
+		self.deserialize_enum_entry(name, value, tagging)
+	}
+}
+
+
+/// Serializes a value into the base of a nested data profile, returning any error from its Serde implementation
+fn serialize_nested<P: MappedProfile, T: Serde<P>>(mut value: T) -> Result<MappedData, DeserializationError> {
+	let mut profile = P::serial_ready();
+	serde_value(&mut value, &mut profile)?;
+	let mut nested = MappedData::serial_ready();
+	swap(&mut nested, &mut profile);
+	Ok(nested)
+}
+
+
+/// Identifies the variant of the serialized fields of an enum according to tagging
+fn tag_enum(variant: &'static str, mut fields: MappedData, tagging: EnumTagging) -> Datum {
+	match tagging {
+		EnumTagging::External if fields.is_empty() => Datum::from(variant),
+		EnumTagging::External => {
+			let mut outer = MappedData::serial_ready();
+			outer.serialize_entry(variant, Datum::Map(fields));
+			Datum::Map(outer)
+		}
+		EnumTagging::Internal(tag) => {
+			fields.serialize_entry(tag, variant);
+			Datum::Map(fields)
+		}
+		EnumTagging::Adjacent { tag, content } => {
+			let mut outer = MappedData::serial_ready();
+			outer.serialize_entry(tag, variant);
+			if !fields.is_empty() {
+				outer.serialize_entry(content, Datum::Map(fields));
+			}
+			Datum::Map(outer)
+		}
+		EnumTagging::Untagged => Datum::Map(fields)
+	}
+}
+
+
+/// Unwraps a map datum, or returns an error naming the given field
+fn expect_map(datum: Datum, field: &str) -> Result<MappedData, DeserializationError> {
+	match datum {
+		Datum::Map(x) => Ok(x),
+		x => Err(DeserializationError::InvalidType { field: field.into(), expected: "map", actual: x.type_name() })
+	}
+}
+
+
+/// Wraps a nested map datum in a data profile, so that it can be deserialized
+fn nested_profile<P: MappedProfile>(datum: Datum, field: &str) -> Result<P, DeserializationError> {
+	let mut profile = P::serial_ready();
+	let options = profile.options();
+	*profile = expect_map(datum, field)?;
+	profile.set_options(options);
+	Ok(profile)
+}
+
+
+impl<P: DataProfile + DerefMut<Target=MappedData>> MappedProfile for P {}
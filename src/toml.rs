@@ -1,9 +1,11 @@
 use std::borrow::Borrow;
-use extern_toml::{de::Error, Value, value::Table};
+use std::collections::VecDeque;
+use std::io::{Read, Write};
+use extern_toml::{de::Error, ser::Error as SerError, Value, value::Table};
 use extern_toml::value::Array;
 
-use crate::datum::Datum;
-use crate::{ArrayData, DataProfile, DeserializationError, Serde};
+use crate::datum::{Datum, widen_f32};
+use crate::{ArrayData, DataProfile, DeserializationError, DeserializeNew, Serde, SerializationError, SerializeRef, TransformResult};
 use crate::profiles::{DatumMap, MappedData};
 use crate::profiles::{ProfileFromData, ProfileToData};
 
@@ -12,9 +14,15 @@ impl DatumMap for Table {
 		let key_string = key.to_key_string();
 		match self.remove(key_string.as_str()) {
 			None => Err(DeserializationError::MissingField(key_string)),
-			Some(x) => Datum::try_from(x)
+			Some(x) => Datum::try_from(x).transform(key_string)
 		}
 	}
+	fn remaining_keys(&self) -> Vec<Datum> {
+		self.keys().map(|key| Datum::from(key.as_str())).collect()
+	}
+	fn boxed_clone(&self) -> Box<dyn DatumMap> {
+		Box::new(self.clone())
+	}
 }
 
 
@@ -25,28 +33,125 @@ impl From<Error> for DeserializationError {
 }
 
 
+/// Creates an error for data that cannot be written as TOML.
+/// The path is filled in by the tables and arrays that the data is in
+fn unwritable(error: SerError) -> SerializationError {
+	SerializationError::TOMLError { field: "".into(), error }
+}
+
+
+/// Prepends the given segment to the path of a TOML error
+fn prepend_field(mut error: SerializationError, segment: &str) -> SerializationError {
+	error.prepend_field(segment);
+	error
+}
+
+
+/// Writes a TOML value as a string.
+/// Errors that are not tied to a field are given the path "\<global\>"
+fn write_toml(value: Result<Value, SerializationError>) -> Result<String, SerializationError> {
+	let value = value.map_err(SerializationError::globalize)?;
+	extern_toml::to_string(&value).map_err(|error| SerializationError::TOMLError { field: "<global>".into(), error })
+}
+
+
+/// Converts the items of an array into a TOML array.
+/// If lossy, the array ends before the first item that TOML cannot express, instead of returning an error
+fn array_to_value(data: ArrayData, lossy: bool) -> Result<Value, SerializationError> {
+	let mut array = Array::new();
+	
+	for (index, (item, _)) in data.into_serialized_items().enumerate() {
+		match datum_to_value(item, lossy) {
+			Ok(item) => array.push(item),
+			Err(_) if lossy => break,
+			Err(e) => return Err(prepend_field(e, &format!("[{}]", index)))
+		}
+	}
+	
+	// The TOML writer does not reject arrays that mix tables with other values, or tables in arrays in arrays,
+	// and writes invalid TOML for them instead
+	let has_tables = array.iter().any(Value::is_table);
+	let invalid = array.iter().position(|item| match item {
+		Value::Table(_) => false,
+		Value::Array(items) => has_tables || items.iter().any(Value::is_table),
+		_ => has_tables
+	});
+	match invalid {
+		Some(index) if !lossy => {
+			let error = SerError::Custom("arrays of tables can only hold tables, and cannot be in other arrays".into());
+			Err(prepend_field(unwritable(error), &format!("[{}]", index)))
+		}
+		_ => Ok(Value::Array(array))
+	}
+}
+
+
+/// Converts the entries of a map into a TOML table.
+/// If lossy, entries that TOML cannot express are left out, instead of returning an error
+fn map_to_value(data: MappedData, lossy: bool) -> Result<Value, SerializationError> {
+	let mut table = Table::new();
+	
+	for (name, value) in data.into_serialized_entries() {
+		let name = name.to_key_string();
+		match datum_to_value(value, lossy) {
+			Ok(value) => { table.insert(name, value); }
+			Err(_) if lossy => {}
+			Err(e) => return Err(prepend_field(e, &name))
+		}
+	}
+	
+	Ok(Value::Table(table))
+}
+
+
+/// TOML integers are signed, so unsigned integers above i64::MAX cannot be converted.
+/// If lossy, such values are left out of the tables and arrays they are in
+fn datum_to_value(datum: Datum, lossy: bool) -> Result<Value, SerializationError> {
+	Ok(match datum {
+		Datum::Bool(b) => b.into(),
+		Datum::U8(n) => (n as i64).into(),
+		Datum::U16(n) => (n as i64).into(),
+		Datum::U32(n) => (n as i64).into(),
+		Datum::U64(n) => i64::try_from(n)
+			.map_err(|_| unwritable(SerError::Custom(format!("{} is over the maximum TOML integer", n))))?
+			.into(),
+		Datum::I8(n) => (n as i64).into(),
+		Datum::I16(n) => (n as i64).into(),
+		Datum::I32(n) => (n as i64).into(),
+		Datum::I64(n) => n.into(),
+		// TOML can express NaN and infinity, so no special handling is needed
+		Datum::F32(n) => widen_f32(n).into(),
+		Datum::F64(n) => n.into(),
+		Datum::String(s) => s.into(),
+		Datum::Map(map) => map_to_value(map, lossy)?,
+		// Datum::Str(s) => s.into(),
+		Datum::Array(arr) => array_to_value(arr, lossy)?
+	})
+}
+
+
+/// Data that TOML cannot express, such as integers above i64::MAX, is left out by into, and arrays end before it.
+/// Use try_into_data to find out about such data instead
 impl ProfileToData<Value> for ArrayData {
 	fn into(self) -> Value {
-		let mut array = Array::new();
-		
-		for (item, _) in self.into_serialized_items() {
-			array.push(item.into());
-		}
-		
-		Value::Array(array)
+		// Nothing is rejected while lossy
+		array_to_value(self, true).unwrap_or_else(|_| Value::Array(Array::new()))
+	}
+	fn try_into_data(self) -> Result<Value, SerializationError> {
+		array_to_value(self, false)
 	}
 }
 
 
+/// Data that TOML cannot express, such as integers above i64::MAX, is left out by into, and arrays end before it.
+/// Use try_into_data to find out about such data instead
 impl ProfileToData<Value> for MappedData {
 	fn into(self) -> Value {
-		let mut table = Table::new();
-		
-		for (name, value) in self.into_serialized_entries() {
-			table.insert(name.to_key_string(), value.into());
-		}
-		
-		Value::Table(table)
+		// Nothing is rejected while lossy
+		map_to_value(self, true).unwrap_or_else(|_| Value::Table(Table::new()))
+	}
+	fn try_into_data(self) -> Result<Value, SerializationError> {
+		map_to_value(self, false)
 	}
 }
 
@@ -55,7 +160,7 @@ impl ProfileFromData<Value> for MappedData {
 	fn try_from(data: Value) -> Result<Self, DeserializationError> {
 		let table = match data {
 			Value::Table(x) => x,
-			_ => return Err(DeserializationError::InvalidType { field: "<global>".into(), expected: "table", actual: "todo!" })
+			x => return Err(DeserializationError::InvalidType { field: "<global>".into(), expected: "table", actual: x.type_str() })
 		};
 		
 		ProfileFromData::try_from(table)
@@ -67,45 +172,85 @@ impl TryFrom<Value> for Datum {
 	type Error = DeserializationError;
 	
 	fn try_from(value: Value) -> Result<Self, Self::Error> {
-		if value.is_table() {
-			return Ok(Self::Map(ProfileFromData::try_from(value)?));
-		}
-		
 		Ok(match value {
 			Value::String(s) => Self::String(s),
+			Value::Integer(n) if n < 0 => Self::I64(n),
 			Value::Integer(n) => Self::U64(n as u64),
-			Value::Table(_) => unreachable!(),
-			_ => todo!()
+			Value::Float(n) => Self::F64(n),
+			Value::Boolean(b) => Self::Bool(b),
+			Value::Array(values) => Self::Array(ProfileFromData::try_from(
+				values.into_iter()
+					.enumerate()
+					.map(|(index, value)| Datum::try_from(value).transform(format!("[{}]", index)))
+					.collect::<Result<VecDeque<_>, _>>()?
+			)?),
+			Value::Table(_) => Self::Map(ProfileFromData::try_from(value)?),
+			// Datetimes are kept in their TOML form, which can be parsed into a datetime type when needed
+			Value::Datetime(d) => Self::String(d.to_string())
 		})
 	}
 }
 
 
-impl From<Datum> for Value {
-	fn from(datum: Datum) -> Self {
-		match datum {
-			Datum::U32(n) => (n as i64).into(),
-			Datum::U64(n) => (n as i64).into(),
-			Datum::String(s) => s.into(),
-			Datum::Map(map) => ProfileToData::into(map),
-			// Datum::Str(s) => s.into(),
-			Datum::Array(arr) => ProfileToData::into(arr)
-		}
+/// TOML integers are signed, so unsigned integers above i64::MAX cannot be converted
+impl TryFrom<Datum> for Value {
+	type Error = SerializationError;
+	
+	fn try_from(datum: Datum) -> Result<Self, Self::Error> {
+		datum_to_value(datum, false)
 	}
 }
 
 
 pub trait TOMLSerde<T: DataProfile + ProfileToData<Value> + ProfileFromData<Value>>: Serde<T> {
-	/// Serializes self into a TOML formatted string
-	///
-	/// # Panics
-	/// This method should not panic. If it does, please report the error to the developer
+	/// Serializes self into a TOML formatted string.
+	/// Panics if serde returns an error, or if the data cannot be written as TOML, such as integers above i64::MAX.
+	/// Use try_serialize_toml to get the error instead
 	fn serialize_toml(self) -> String {
-		extern_toml::to_string(&self.serialize::<Value>()).expect("An error occurred during TOML Serialization. Please report this to the developer")
+		self.try_serialize_toml().unwrap_or_else(|e| panic!("{}", e))
+	}
+	/// Serializes self into a TOML formatted string.
+	/// Returns an error if serde returns an error, or if the data cannot be written as TOML
+	fn try_serialize_toml(self) -> Result<String, SerializationError> {
+		write_toml(self.try_serialize::<Value>())
+	}
+	/// Serializes self into TOML, writing it to the given writer, such as a file or a socket.
+	/// Returns an error if serde returns an error, if the data cannot be written as TOML, or if the writer fails
+	fn serialize_toml_to<W: Write>(self, mut writer: W) -> Result<(), SerializationError> {
+		writer.write_all(self.try_serialize_toml()?.as_bytes())?;
+		Ok(())
+	}
+	/// Serializes self into a TOML formatted string, leaving self intact.
+	/// Panics if the data cannot be written as TOML.
+	/// Use try_serialize_toml_ref to get the error instead
+	fn serialize_toml_ref(&self) -> String where Self: SerializeRef<T> {
+		self.try_serialize_toml_ref().unwrap_or_else(|e| panic!("{}", e))
+	}
+	/// Serializes self into a TOML formatted string, leaving self intact.
+	/// Returns an error if the data cannot be written as TOML
+	fn try_serialize_toml_ref(&self) -> Result<String, SerializationError> where Self: SerializeRef<T> {
+		write_toml(self.to_data_profile().try_into_data())
 	}
 	/// Deserializes a string type into Self.
 	/// Returns an error if the string could not be deserialized
-	fn deserialize_toml<S: Borrow<str>>(data: S) -> Result<Self, DeserializationError> {
+	fn deserialize_toml<S: Borrow<str>>(data: S) -> Result<Self, DeserializationError> where Self: Default {
 		Self::deserialize::<Value>(data.borrow().parse()?)
 	}
+	/// Deserializes Self from TOML read from the given reader, such as a file or a socket.
+	/// The reader is read to the end before deserializing
+	fn deserialize_toml_from<R: Read>(mut reader: R) -> Result<Self, DeserializationError> where Self: Default {
+		let mut data = String::new();
+		reader.read_to_string(&mut data)?;
+		Self::deserialize_toml(data)
+	}
+	/// Deserializes a string type into self, updating the existing value.
+	/// Returns an error if the string could not be deserialized
+	fn deserialize_toml_into<S: Borrow<str>>(&mut self, data: S) -> Result<(), DeserializationError> {
+		self.deserialize_into::<Value>(data.borrow().parse()?)
+	}
+	/// Constructs Self from a string type.
+	/// Returns an error if the string could not be deserialized
+	fn deserialize_toml_new<S: Borrow<str>>(data: S) -> Result<Self, DeserializationError> where Self: DeserializeNew<T> {
+		Self::deserialize_new::<Value>(data.borrow().parse()?)
+	}
 }